@@ -0,0 +1,333 @@
+//! Anchor IDs of speeches.
+//!
+//! Each speech heading gets an ID so that it can be linked from the cast list or other pages.
+//! `IdScheme` chooses how IDs are made:
+//!
+//! - `Counter` numbers speeches in the document: `D0`, `D1`, ...
+//! - `Scene` numbers speeches in each scene: `D-acte-i-scène-i-0`, ...
+//! - `Character` numbers speeches of each character: `D-figaro-0`, ...
+//! - `Hash` uses a hash of the character and the text of the speech: `D-1a2b3c4d`.
+//!
+//! The IDs are unique in a document; a suffix such as `-2` is appended to a duplicate.
+//! `SpeechIds::reset` starts a new document.
+//!
+//! The cast list links to the first speeches of characters before they appear.
+//! `SpeechIds::reserve` predicts their IDs from the speeches read ahead, so that they have
+//! the same IDs as without the cast list.
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// A scheme of anchor IDs of speeches.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub enum IdScheme {
+    #[default]
+    Counter,
+    Scene,
+    Character,
+    Hash,
+}
+
+impl FromStr for IdScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "counter" => Ok(IdScheme::Counter),
+            "scene" => Ok(IdScheme::Scene),
+            "character" => Ok(IdScheme::Character),
+            "hash" => Ok(IdScheme::Hash),
+            _ => Err(format!("unknown ID scheme: {}", s)),
+        }
+    }
+}
+
+/// A speech which is read ahead to predict its ID.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SpeechKey {
+    /// The act and the scene which the speech belongs to.
+    pub act: Option<String>,
+    pub scene: Option<String>,
+    pub character: String,
+    /// The text of the body, which is hashed by `IdScheme::Hash`.
+    pub text: String,
+}
+
+/// A generator of anchor IDs of speeches in a document.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SpeechIds {
+    scheme: IdScheme,
+    prefix: String,
+    /// The slug of the current act and scene.
+    scene: String,
+    counter: usize,
+    counts: HashMap<String, usize>,
+    used: HashSet<String>,
+    /// The IDs of the first speeches of characters.
+    first: HashMap<String, String>,
+    /// The IDs which were given to the first speeches of characters before they appear.
+    reserved: HashMap<String, String>,
+}
+
+impl Default for SpeechIds {
+    fn default() -> Self {
+        Self::new(IdScheme::default())
+    }
+}
+
+impl SpeechIds {
+    pub fn new(scheme: IdScheme) -> Self {
+        Self {
+            scheme,
+            prefix: "D".to_owned(),
+            scene: String::new(),
+            counter: 0,
+            counts: HashMap::new(),
+            used: HashSet::new(),
+            first: HashMap::new(),
+            reserved: HashMap::new(),
+        }
+    }
+
+    pub fn scheme(&self) -> IdScheme {
+        self.scheme
+    }
+
+    /// Sets the prefix of IDs, which is `D` by default.
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_owned();
+    }
+
+    /// Forgets the IDs given so far and starts a new document.
+    pub fn reset(&mut self) {
+        *self = Self {
+            prefix: std::mem::take(&mut self.prefix),
+            ..Self::new(self.scheme)
+        };
+    }
+
+    /// Moves to the act and the scene which the following speeches belong to.
+    pub fn enter_scene(&mut self, act: Option<&str>, scene: Option<&str>) {
+        self.scene = join([act.map(slugify), scene.map(slugify)].iter().flatten());
+    }
+
+    /// Returns the ID of the next speech.
+    pub fn next_id(&mut self, character: &str, text: &str) -> String {
+        let index = self.counter;
+        self.counter += 1;
+        let key = match self.scheme {
+            IdScheme::Scene => Some(self.scene.clone()),
+            IdScheme::Character => Some(slugify(character)),
+            _ => None,
+        };
+        let count = key.map(|key| {
+            let count = self.counts.entry(key.clone()).or_insert(0);
+            *count += 1;
+            (key, *count - 1)
+        });
+
+        if let Some(id) = self.reserved.remove(character) {
+            self.first.insert(character.to_owned(), id.clone());
+            return id;
+        }
+
+        let id = match (self.scheme, count) {
+            (IdScheme::Hash, _) => self.join_prefix(&[format!("{:08x}", hash(character, text))]),
+            (_, Some((key, count))) => self.join_prefix(&[key, count.to_string()]),
+            (_, None) => format!("{}{}", self.prefix, index),
+        };
+        let id = self.unique(id);
+        self.first.entry(character.to_owned()).or_insert_with(|| id.clone());
+
+        id
+    }
+
+    /// Reserves the IDs of the first speeches of characters in the speeches read ahead.
+    ///
+    /// The IDs are the ones which the scheme gives to the speeches when they come.
+    pub fn reserve(&mut self, speeches: &[SpeechKey]) {
+        let mut ahead = self.clone();
+
+        for speech in speeches.iter() {
+            ahead.enter_scene(speech.act.as_deref(), speech.scene.as_deref());
+            let id = ahead.next_id(&speech.character, &speech.text);
+            if !self.first.contains_key(&speech.character) && !self.reserved.contains_key(&speech.character) {
+                self.used.insert(id.clone());
+                self.reserved.insert(speech.character.clone(), id);
+            }
+        }
+    }
+
+    /// Returns the ID of the first speech of a character, which is the speech of the given
+    /// index in the document.
+    ///
+    /// If the speech has neither been given an ID nor been reserved by `SpeechIds::reserve`,
+    /// an ID made of the index or the character is reserved for it.
+    pub fn first_speech_id(&mut self, character: &str, index: usize) -> String {
+        if let Some(id) = self.first.get(character).or_else(|| self.reserved.get(character)) {
+            return id.clone();
+        }
+
+        let id = match self.scheme {
+            IdScheme::Counter => format!("{}{}", self.prefix, index),
+            IdScheme::Character => self.join_prefix(&[slugify(character), "0".to_owned()]),
+            _ => self.join_prefix(&[slugify(character)]),
+        };
+        let id = self.unique(id);
+        self.reserved.insert(character.to_owned(), id.clone());
+
+        id
+    }
+
+    fn join_prefix(&self, parts: &[String]) -> String {
+        join(std::iter::once(&self.prefix).chain(parts.iter()))
+    }
+
+    fn unique(&mut self, id: String) -> String {
+        let mut unique = id.clone();
+        let mut n = 2;
+        while self.used.contains(&unique) {
+            unique = format!("{}-{}", id, n);
+            n += 1;
+        }
+        self.used.insert(unique.clone());
+
+        unique
+    }
+}
+
+fn join<'a, I>(parts: I) -> String
+where
+    I: Iterator<Item=&'a String>,
+{
+    parts
+        .filter(|part| !part.is_empty())
+        .map(|part| part.as_str())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Makes a part of IDs from a title or a name.
+///
+/// Letters and digits are lowercased and the others are replaced by `-`.
+pub fn slugify(s: &str) -> String {
+    let mut slug = String::new();
+
+    for c in s.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Hashes a speech with 32-bit FNV-1a, which is stable across platforms and compilers.
+fn hash(character: &str, text: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+
+    for byte in character.bytes().chain(std::iter::once(0)).chain(text.bytes()) {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn slugify_titles() {
+        assert_eq!(slugify("Acte Premier"), "acte-premier");
+        assert_eq!(slugify("Scène I."), "scène-i");
+        assert_eq!(slugify("第一幕　湯島"), "第一幕-湯島");
+    }
+
+    #[test]
+    fn number_speeches_in_scenes() {
+        let mut ids = SpeechIds::new(IdScheme::Scene);
+        ids.enter_scene(Some("Acte I"), Some("Scène I"));
+        assert_eq!(ids.next_id("Figaro", "Dix-neuf pieds."), "D-acte-i-scène-i-0");
+        assert_eq!(ids.next_id("Suzanne", "Quoi ?"), "D-acte-i-scène-i-1");
+        ids.enter_scene(Some("Acte I"), Some("Scène II"));
+        assert_eq!(ids.next_id("Figaro", "Bonjour."), "D-acte-i-scène-ii-0");
+    }
+
+    #[test]
+    fn make_ids_unique() {
+        let mut ids = SpeechIds::new(IdScheme::Hash);
+        let first = ids.next_id("A", "Hello!");
+        assert_eq!(ids.next_id("A", "Hello!"), format!("{}-2", first));
+
+        let mut ids = SpeechIds::new(IdScheme::Character);
+        assert_eq!(ids.next_id("A B", "x"), "D-a-b-0");
+        assert_eq!(ids.next_id("a-b", "y"), "D-a-b-1");
+    }
+
+    #[test]
+    fn reserve_ids_of_first_speeches() {
+        let mut ids = SpeechIds::new(IdScheme::Scene);
+        assert_eq!(ids.first_speech_id("Figaro", 0), "D-figaro");
+        assert_eq!(ids.next_id("Figaro", "Hello!"), "D-figaro");
+        assert_eq!(ids.next_id("Figaro", "Hello!"), "D-1");
+        assert_eq!(ids.first_speech_id("Figaro", 0), "D-figaro");
+
+        ids.reset();
+        assert_eq!(ids.first_speech_id("Figaro", 1), "D-figaro");
+        assert_eq!(ids.next_id("Suzanne", "Hi."), "D-0");
+    }
+
+    #[test]
+    fn reserve_ids_of_speeches_read_ahead() {
+        let speeches = |act: &str, scene: &str, characters: &[&str]| -> Vec<SpeechKey> {
+            characters.iter()
+                .map(|character| SpeechKey {
+                    act: Some(act.to_owned()),
+                    scene: Some(scene.to_owned()),
+                    character: character.to_string(),
+                    text: "Hello!".to_owned(),
+                })
+                .collect()
+        };
+        let mut ahead = speeches("Acte I", "Scène I", &["Figaro", "Suzanne"]);
+        ahead.extend(speeches("Acte I", "Scène II", &["Figaro", "Bartholo"]));
+
+        for &scheme in [IdScheme::Counter, IdScheme::Scene, IdScheme::Character, IdScheme::Hash].iter() {
+            let mut expected = SpeechIds::new(scheme);
+            let expected: Vec<String> = ahead.iter()
+                .map(|speech| {
+                    expected.enter_scene(speech.act.as_deref(), speech.scene.as_deref());
+                    expected.next_id(&speech.character, &speech.text)
+                })
+                .collect();
+
+            let mut ids = SpeechIds::new(scheme);
+            ids.reserve(&ahead);
+            assert_eq!(ids.first_speech_id("Bartholo", 3), expected[3]);
+            assert_eq!(ids.first_speech_id("Suzanne", 1), expected[1]);
+            let actual: Vec<String> = ahead.iter()
+                .map(|speech| {
+                    ids.enter_scene(speech.act.as_deref(), speech.scene.as_deref());
+                    ids.next_id(&speech.character, &speech.text)
+                })
+                .collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn keep_counter_ids() {
+        let mut ids = SpeechIds::default();
+        ids.set_prefix("c1-D");
+        assert_eq!(ids.next_id("A", ""), "c1-D0");
+        assert_eq!(ids.first_speech_id("B", 2), "c1-D2");
+        assert_eq!(ids.next_id("C", ""), "c1-D1");
+        assert_eq!(ids.next_id("B", ""), "c1-D2");
+        ids.reset();
+        assert_eq!(ids.next_id("A", ""), "c1-D0");
+    }
+}
@@ -0,0 +1,63 @@
+use std::io;
+use std::process;
+use eyre::{eyre, Result, WrapErr};
+use structopt::StructOpt;
+use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+use mdplayscript::preprocessor::PlayScriptPreprocessor;
+
+#[derive(Debug,StructOpt)]
+#[structopt(about = "An mdbook preprocessor for play scripts")]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug,StructOpt)]
+enum Command {
+    /// Checks whether a renderer is supported by this preprocessor
+    Supports {
+        renderer: String,
+    },
+}
+
+fn handle_preprocessing(pre: &dyn Preprocessor) -> Result<()> {
+    let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())
+        .map_err(|e| eyre!("{:?}", e))
+        .wrap_err("failed to parse the book from stdin")?;
+
+    if ctx.mdbook_version != mdbook::MDBOOK_VERSION {
+        eprintln!(
+            "Warning: The {} plugin was built against version {} of mdbook, \
+             but we're being called from version {}",
+            pre.name(),
+            mdbook::MDBOOK_VERSION,
+            ctx.mdbook_version
+        );
+    }
+
+    let processed = pre.run(&ctx, book)
+        .map_err(|e| eyre!("{:?}", e))?;
+    serde_json::to_writer(io::stdout(), &processed)
+        .wrap_err("failed to write the book to stdout")?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // Diagnostics of chapters are logged to stderr, which mdbook shows.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let opt = Opt::from_args();
+    let pre = PlayScriptPreprocessor::new();
+
+    match opt.command {
+        Some(Command::Supports { renderer }) => {
+            if pre.supports_renderer(&renderer) {
+                Ok(())
+            } else {
+                process::exit(1);
+            }
+        },
+        None => handle_preprocessing(&pre),
+    }
+}
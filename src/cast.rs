@@ -0,0 +1,183 @@
+//! Declaration of the cast.
+//!
+//! The cast is declared by a `playscript-cast` directive. Each line of the directive is a
+//! canonical name of a character optionally followed by aliases in parentheses, and `:` and
+//! a description.
+//!
+//! ```text
+//! <!-- playscript-cast
+//! Figaro (F, Fig): valet de chambre du comte
+//! Suzanne (S): première camariste de la comtesse
+//! -->
+//! ```
+//!
+//! Speech headings written with aliases are resolved into the canonical names.
+//!
+//! The `playscript-cast-list` directive generates the list of characters who appear in the
+//! script, with the descriptions in the declaration.
+use crate::diagnostics::suggest;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct CastMember {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Cast {
+    pub members: Vec<CastMember>,
+}
+
+impl Cast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the content of a `playscript-cast` directive.
+    pub fn parse(s: &str) -> Self {
+        let mut cast = Cast::new();
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, description) = match line.find(':') {
+                Some(pos) => {
+                    let description = line[pos+1..].trim();
+                    let description = if description.is_empty() {
+                        None
+                    } else {
+                        Some(description.to_owned())
+                    };
+                    (line[..pos].trim(), description)
+                },
+                None => (line, None),
+            };
+
+            let (name, aliases) = match (name.find('('), name.strip_suffix(')')) {
+                (Some(pos), Some(stripped)) => {
+                    let aliases = stripped[pos+1..].split(',')
+                        .map(|alias| alias.trim())
+                        .filter(|alias| !alias.is_empty())
+                        .map(|alias| alias.to_owned())
+                        .collect();
+                    (name[..pos].trim(), aliases)
+                },
+                _ => (name, Vec::new()),
+            };
+
+            cast.push(CastMember {
+                name: name.to_owned(),
+                aliases,
+                description,
+            });
+        }
+
+        cast
+    }
+
+    /// Adds a member. The aliases and the description are updated if the member is already
+    /// declared.
+    pub fn push(&mut self, member: CastMember) {
+        match self.members.iter_mut().find(|m| m.name == member.name) {
+            Some(m) => {
+                for alias in member.aliases.into_iter() {
+                    if !m.aliases.contains(&alias) {
+                        m.aliases.push(alias);
+                    }
+                }
+                if member.description.is_some() {
+                    m.description = member.description;
+                }
+            },
+            None => self.members.push(member),
+        }
+    }
+
+    pub fn extend(&mut self, other: Cast) {
+        for member in other.members.into_iter() {
+            self.push(member);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CastMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Returns the canonical name of a name or an alias.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        self.members.iter()
+            .find(|m| m.name == name)
+            .or_else(|| self.members.iter().find(|m| m.aliases.iter().any(|alias| alias == name)))
+            .map(|m| m.name.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.members.iter().map(|m| m.name.as_str())
+    }
+
+    /// Returns the declared name or alias nearest to an undeclared name.
+    pub fn suggest(&self, name: &str) -> Option<&str> {
+        let aliases = self.members.iter()
+            .flat_map(|m| m.aliases.iter().map(|alias| alias.as_str()));
+        suggest(name, self.names().chain(aliases))
+    }
+}
+
+/// An entry of the list of characters generated by the `playscript-cast-list` directive.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CastListEntry {
+    pub name: String,
+    pub description: Option<String>,
+    /// The index of the first speech of the character in the document.
+    pub first_speech: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_cast_declaration() {
+        let cast = Cast::parse("\nFigaro: valet de chambre du comte\n  Suzanne  \nFigaro:\n");
+        assert_eq!(cast.members, vec![
+            CastMember {
+                name: "Figaro".to_owned(),
+                aliases: vec![],
+                description: Some("valet de chambre du comte".to_owned()),
+            },
+            CastMember {
+                name: "Suzanne".to_owned(),
+                aliases: vec![],
+                description: None,
+            },
+        ]);
+        assert!(cast.contains("Suzanne"));
+        assert_eq!(cast.suggest("Suzane"), Some("Suzanne"));
+        assert_eq!(cast.suggest("Bartholo"), None);
+    }
+
+    #[test]
+    fn resolve_aliases() {
+        let mut cast = Cast::parse("Figaro (F, Fig): valet\nLe Comte Almaviva (Le Comte)");
+        cast.extend(Cast::parse("Figaro (Fi)"));
+        assert_eq!(cast.members[0].aliases, vec!["F".to_owned(), "Fig".to_owned(), "Fi".to_owned()]);
+        assert_eq!(cast.members[0].description.as_deref(), Some("valet"));
+        assert_eq!(cast.resolve("Fig"), Some("Figaro"));
+        assert_eq!(cast.resolve("Figaro"), Some("Figaro"));
+        assert_eq!(cast.resolve("Le Comte"), Some("Le Comte Almaviva"));
+        assert_eq!(cast.resolve("Suzanne"), None);
+    }
+}
@@ -0,0 +1,664 @@
+//! Diagnostics of play scripts.
+//!
+//! `lint` parses a play script in the same way as `MdPlayScript` and reports mistakes which
+//! are silently accepted by the parser, such as unclosed parentheses, unknown directives and
+//! unterminated monologues. Empty speeches and characters which are neither in the given
+//! list nor declared by `playscript-cast` directives are also reported, and so are aliases
+//! used before their declarations, which the renderer does not resolve. Each diagnostic has
+//! the line and the column in the source, which are computed from the offsets given by
+//! pulldown-cmark.
+use std::fmt;
+use std::ops::Range;
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::Serialize;
+use crate::cast::Cast;
+use crate::interface::{Mode, Options, Directive, CommentJoiner, parse_directive, markdown_options};
+use crate::parser::{Delimiters, split_speech_heading_with, is_speech_start_with};
+use crate::speech::{self, parse_speech_with};
+use crate::ruby::strip_ruby;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// `(` without the corresponding `)`, or the same with other brackets accepted by
+    /// `Options::delimiters`. The rest of the speech becomes a direction.
+    UnclosedParenthesis,
+    /// `)` without the corresponding `(`, or the same with other accepted brackets.
+    UnmatchedParenthesis,
+    /// An HTML comment which looks like a directive but is not one.
+    UnknownDirective,
+    /// `playscript-monologue-begin` without `playscript-monologue-end`.
+    UnterminatedMonologue,
+    /// `playscript-monologue-end` without `playscript-monologue-begin`.
+    UnmatchedMonologueEnd,
+    /// A speech which has no content.
+    EmptySpeech,
+    /// A character who is not in the list of known characters.
+    UnknownCharacter,
+    /// An alias which is used before the `playscript-cast` directive declaring it. It is not
+    /// resolved into the canonical name.
+    AliasBeforeDeclaration,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub message: String,
+    /// Line number starting from 1.
+    pub line: usize,
+    /// Column number in characters starting from 1.
+    pub column: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}: {}", self.line, self.column, self.severity, self.message)
+    }
+}
+
+/// A collector of diagnostics which converts byte offsets into lines and columns.
+#[derive(Debug,Clone)]
+pub struct Diagnostics<'s> {
+    source: &'s str,
+    line_starts: Vec<usize>,
+    items: Vec<Diagnostic>,
+}
+
+impl<'s> Diagnostics<'s> {
+    pub fn new(source: &'s str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(pos, _)| pos + 1));
+
+        Self {
+            source,
+            line_starts,
+            items: Vec::new(),
+        }
+    }
+
+    /// Returns the line and the column of a byte offset.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = std::cmp::min(offset, self.source.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let start = self.line_starts[line];
+        let column = self.source[start..offset].chars().count();
+
+        (line + 1, column + 1)
+    }
+
+    pub fn push<S: Into<String>>(&mut self, severity: Severity, kind: DiagnosticKind, offset: usize, message: S) {
+        let (line, column) = self.position(offset);
+
+        self.items.push(Diagnostic {
+            severity,
+            kind,
+            message: message.into(),
+            line,
+            column,
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.items.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    /// Returns the number of diagnostics of a severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.items.iter().filter(|d| d.severity == severity).count()
+    }
+
+    /// Returns the diagnostics sorted by their positions.
+    pub fn into_vec(mut self) -> Vec<Diagnostic> {
+        self.items.sort_by_key(|d| (d.line, d.column));
+        self.items
+    }
+}
+
+/// Returns the Levenshtein distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + std::cmp::min(prev, std::cmp::min(row[j], row[j + 1]))
+            };
+            prev = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Returns the candidate nearest to the word if it is close enough.
+pub fn suggest<'c, I>(word: &str, candidates: I) -> Option<&'c str>
+where
+    I: IntoIterator<Item=&'c str>,
+{
+    let threshold = std::cmp::max(1, word.chars().count() / 3);
+
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+const DIRECTIVES: [&str; 10] = [
+    "playscript-monologue-begin",
+    "playscript-monologue-end",
+    "playscript-on",
+    "playscript-off",
+    "playscript-title",
+    "playscript-subtitle",
+    "playscript-authors",
+    "playscript-make-title",
+    "playscript-cast",
+    "playscript-cast-list",
+];
+
+/// Returns the content of an HTML comment.
+fn comment_content(s: &str) -> Option<&str> {
+    let s = s.trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim();
+
+    Some(s)
+}
+
+/// Returns the source offset of a byte index in a text event.
+fn source_offset(text: &str, range: &Range<usize>, index: usize) -> usize {
+    if range.end - range.start == text.len() {
+        range.start + index
+    } else {
+        range.start
+    }
+}
+
+struct Linter<'s, 'c> {
+    diagnostics: Diagnostics<'s>,
+    mode: Mode,
+    monologue_begin: Option<usize>,
+    characters: Option<&'c [String]>,
+    /// The cast declared in the whole play script.
+    cast: Cast,
+    /// The cast declared so far, which resolves aliases as the renderer does.
+    declared: Cast,
+    delimiters: Delimiters,
+}
+
+impl<'s, 'c> Linter<'s, 'c> {
+    fn dispatch_directive(&mut self, s: &str, offset: usize) {
+        let content = match comment_content(s) {
+            Some(content) => content,
+            None => return,
+        };
+
+        let directive = match parse_directive(s) {
+            Some(directive) => directive,
+            None => {
+                if content.starts_with("playscript") {
+                    let message = match suggest(content, DIRECTIVES.iter().cloned()) {
+                        Some(candidate) => format!("unknown directive `{}`; did you mean `{}`?", content, candidate),
+                        None => format!("unknown directive `{}`", content),
+                    };
+                    self.diagnostics.push(Severity::Warning, DiagnosticKind::UnknownDirective, offset, message);
+                }
+                return;
+            },
+        };
+
+        match directive {
+            Directive::MonologueBegin => {
+                self.close_monologue();
+                self.monologue_begin = Some(offset);
+                self.mode = Mode::Monologue;
+            },
+            Directive::MonologueEnd => {
+                if self.monologue_begin.take().is_none() {
+                    self.diagnostics.push(Severity::Warning, DiagnosticKind::UnmatchedMonologueEnd, offset,
+                        "`playscript-monologue-end` without `playscript-monologue-begin`");
+                }
+                self.mode = Mode::PlayScript;
+            },
+            Directive::PlayScriptOn => {
+                self.close_monologue();
+                self.mode = Mode::PlayScript;
+            },
+            Directive::PlayScriptOff => {
+                self.close_monologue();
+                self.mode = Mode::Nop;
+            },
+            Directive::Cast(cast) => {
+                self.declared.extend(cast);
+            },
+            _ => {},
+        }
+    }
+
+    /// Reports the monologue which is not terminated by `playscript-monologue-end`.
+    fn close_monologue(&mut self) {
+        if let Some(offset) = self.monologue_begin.take() {
+            self.diagnostics.push(Severity::Error, DiagnosticKind::UnterminatedMonologue, offset,
+                "`playscript-monologue-begin` is not terminated by `playscript-monologue-end`");
+        }
+    }
+
+    fn check_paragraph(&mut self, events: Vec<(Event<'s>, Range<usize>)>) {
+        let mut speech = Vec::new();
+        let mut at_line_head = true;
+
+        for (event, range) in events.into_iter() {
+            let starts_speech = match &event {
+                Event::Text(s) => at_line_head && is_speech_start_with(s, &self.delimiters),
+                _ => false,
+            };
+            at_line_head = matches!(event, Event::SoftBreak | Event::HardBreak);
+            if starts_speech && !speech.is_empty() {
+                self.check_speech(std::mem::take(&mut speech));
+            }
+            speech.push((event, range));
+        }
+
+        if !speech.is_empty() {
+            self.check_speech(speech);
+        }
+    }
+
+    fn check_speech(&mut self, events: Vec<(Event<'s>, Range<usize>)>) {
+        let heading_end = match events.first() {
+            Some((Event::Text(s), range)) => split_speech_heading_with(s, &self.delimiters)
+                .map(|(heading, body)| {
+                    self.check_heading(heading, s, range);
+                    s.len() - body.len()
+                }),
+            _ => None,
+        };
+
+        if heading_end.is_none() && !self.mode.is_monologue() {
+            return;
+        }
+
+        if heading_end.is_some() {
+            self.check_speech_content(&events);
+        }
+
+        let mut open_parens = Vec::new();
+
+        for (index, (event, range)) in events.iter().enumerate() {
+            let text = match event {
+                Event::Text(s) => s.as_ref(),
+                _ => continue,
+            };
+            let start = if index == 0 { heading_end.unwrap_or(0) } else { 0 };
+
+            for (pos, c, len) in paren_runs(&text[start..], &self.delimiters) {
+                if len > 1 {
+                    continue;
+                }
+                let offset = source_offset(text, range, start + pos);
+                if self.delimiters.is_open(c) {
+                    open_parens.push((offset, c));
+                } else if open_parens.pop().is_none() {
+                    self.diagnostics.push(Severity::Warning, DiagnosticKind::UnmatchedParenthesis, offset,
+                        format!("`{}` without the corresponding `{}`", c, opening_of(c)));
+                }
+            }
+        }
+
+        if let Some((offset, c)) = open_parens.first() {
+            self.diagnostics.push(Severity::Error, DiagnosticKind::UnclosedParenthesis, *offset,
+                format!("`{}` is not closed; the rest of the speech becomes a direction", c));
+        }
+    }
+
+    fn check_speech_content(&mut self, events: &[(Event<'s>, Range<usize>)]) {
+        let offset = events[0].1.start;
+        let speech = match parse_speech_with(events.iter().map(|(e, _)| e.clone()).collect(), &self.delimiters) {
+            Ok(speech) => speech,
+            Err(_) => return,
+        };
+        let character = strip_ruby(&speech.heading.character);
+        let character = character.as_str();
+
+        if !speech.body.iter().any(is_content) {
+            self.diagnostics.push(Severity::Warning, DiagnosticKind::EmptySpeech, offset,
+                format!("speech of `{}` is empty", character));
+        }
+
+        let characters = self.characters.unwrap_or(&[]);
+        let has_declaration = !characters.is_empty() || !self.cast.is_empty();
+        let is_declared = characters.iter().any(|c| c == character) || self.cast.resolve(character).is_some();
+        if has_declaration && !is_declared {
+            let candidates = characters.iter().map(|c| c.as_str()).chain(self.cast.names());
+            let message = match suggest(character, candidates) {
+                Some(candidate) => format!("unknown character `{}`; did you mean `{}`?", character, candidate),
+                None => format!("unknown character `{}`", character),
+            };
+            self.diagnostics.push(Severity::Error, DiagnosticKind::UnknownCharacter, offset, message);
+        } else if self.declared.resolve(character).is_none() {
+            if let Some(name) = self.cast.resolve(character).filter(|name| *name != character) {
+                let message = format!("alias `{}` of `{}` is used before its declaration", character, name);
+                self.diagnostics.push(Severity::Error, DiagnosticKind::AliasBeforeDeclaration, offset, message);
+            }
+        }
+    }
+
+    fn check_heading(&mut self, heading: &str, text: &str, range: &Range<usize>) {
+        let delimiters = self.delimiters;
+        if let Some((pos, c)) = heading.char_indices().find(|(_, c)| delimiters.is_open(*c)) {
+            if !matches!(delimiters.close_of(c), Some(close) if heading[pos..].contains(close)) {
+                self.diagnostics.push(Severity::Error, DiagnosticKind::UnclosedParenthesis,
+                    source_offset(text, range, pos),
+                    format!("`{}` in the speech heading is not closed", c));
+            }
+        }
+    }
+}
+
+fn is_content(inline: &speech::Inline) -> bool {
+    match inline {
+        speech::Inline::Event(Event::Text(s)) => !s.trim().is_empty(),
+        speech::Inline::Event(Event::SoftBreak) | speech::Inline::Event(Event::HardBreak) => false,
+        _ => true,
+    }
+}
+
+/// Returns the opening bracket which corresponds to a closing one.
+fn opening_of(c: char) -> char {
+    match c {
+        '）' => '（',
+        '〕' => '〔',
+        _ => '(',
+    }
+}
+
+/// Returns the positions, the characters and the lengths of runs of parentheses.
+fn paren_runs(s: &str, delimiters: &Delimiters) -> Vec<(usize, char, usize)> {
+    let mut runs: Vec<(usize, char, usize)> = Vec::new();
+
+    for (pos, c) in s.char_indices() {
+        if !delimiters.is_open(c) && !delimiters.is_close(c) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((start, last, len)) if *last == c && *start + *len * c.len_utf8() == pos => *len += 1,
+            _ => runs.push((pos, c, 1)),
+        }
+    }
+
+    runs
+}
+
+/// Collects the cast declared in a play script.
+fn declared_cast(source: &str) -> Cast {
+    let mut cast = Cast::new();
+    let mut comments = CommentJoiner::default();
+
+    for event in Parser::new_ext(source, markdown_options()) {
+        if let Event::Html(s) = event {
+            if let Some(Directive::Cast(declared)) = comments.push(&s).and_then(|html| parse_directive(&html)) {
+                cast.extend(declared);
+            }
+        }
+    }
+
+    cast
+}
+
+/// A configuration of checks.
+#[derive(Debug,Clone)]
+pub struct Lint {
+    options: Options,
+    characters: Option<Vec<String>>,
+}
+
+impl Lint {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            characters: None,
+        }
+    }
+
+    /// Sets the list of known characters. Speeches of other characters are reported.
+    pub fn characters(self, characters: Vec<String>) -> Self {
+        Self {
+            characters: Some(characters),
+            ..self
+        }
+    }
+
+    /// Reports mistakes in a play script.
+    pub fn run<'s>(&self, source: &'s str) -> Diagnostics<'s> {
+        let mut linter = Linter {
+            cast: declared_cast(source),
+            declared: Cast::new(),
+            diagnostics: Diagnostics::new(source),
+            mode: if self.options.disabled_in_default {
+                Mode::Nop
+            } else {
+                Mode::PlayScript
+            },
+            monologue_begin: None,
+            characters: self.characters.as_deref(),
+            delimiters: self.options.delimiters,
+        };
+
+        let mut iter = Parser::new_ext(source, markdown_options()).into_offset_iter();
+        let mut comments = CommentJoiner::default();
+        let mut comment_start = None;
+
+        while let Some((event, range)) = iter.next() {
+            match event {
+                Event::Html(s) => {
+                    let start = *comment_start.get_or_insert(range.start);
+                    if let Some(html) = comments.push(&s) {
+                        linter.dispatch_directive(&html, start);
+                        comment_start = None;
+                    }
+                },
+                Event::Start(Tag::Paragraph) if !linter.mode.is_off() => {
+                    let events = iter.by_ref()
+                        .take_while(|(e, _)| *e != Event::End(Tag::Paragraph))
+                        .collect();
+                    linter.check_paragraph(events);
+                },
+                _ => {},
+            }
+        }
+
+        linter.close_monologue();
+
+        linter.diagnostics
+    }
+}
+
+/// Reports mistakes in a play script with the default configuration.
+pub fn lint<'s>(source: &'s str, options: &Options) -> Diagnostics<'s> {
+    Lint::new(options.clone()).run(source)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn check(s: &str) -> Vec<(usize, usize, DiagnosticKind)> {
+        lint(s, &Options::default()).into_vec()
+            .into_iter()
+            .map(|d| (d.line, d.column, d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn compute_positions() {
+        let diagnostics = Diagnostics::new("ab\nこんにちは\n\nx");
+        assert_eq!(diagnostics.position(0), (1, 1));
+        assert_eq!(diagnostics.position(3), (2, 1));
+        assert_eq!(diagnostics.position(9), (2, 3));
+        assert_eq!(diagnostics.position(19), (3, 1));
+        assert_eq!(diagnostics.position(20), (4, 1));
+    }
+
+    #[test]
+    fn report_parentheses() {
+        let s = "A> Hello (running\nB> Bye) (exit)\nC> Escaped (( and )) parens.";
+        assert_eq!(check(s), vec![
+            (1, 10, DiagnosticKind::UnclosedParenthesis),
+            (2, 7, DiagnosticKind::UnmatchedParenthesis),
+        ]);
+        assert_eq!(check("A (running> Hello"), vec![
+            (1, 3, DiagnosticKind::UnclosedParenthesis),
+        ]);
+        assert!(check("Normal (paragraph.").is_empty());
+    }
+
+    #[test]
+    fn report_full_width_parentheses() {
+        let messages: Vec<String> = lint("お蔦＞ 貴方（泣く\n主税＞ 蔦）", &Options::default_ja()).into_vec()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(messages, vec![
+            "1:7: error: `（` is not closed; the rest of the speech becomes a direction",
+            "2:6: warning: `）` without the corresponding `（`",
+        ]);
+        assert!(lint("お蔦＞ 貴方（泣く", &Options::default()).is_empty());
+        let messages: Vec<String> = lint("お蔦（泣いて)＞ 貴方。", &Options::default_ja()).into_vec()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(messages, vec![
+            "1:3: error: `（` in the speech heading is not closed",
+        ]);
+    }
+
+    #[test]
+    fn report_unknown_directives() {
+        let diagnostics = lint("<!-- playscript-monolog-begin -->\n\n<!-- comment -->\n", &Options::default());
+        assert!(!diagnostics.has_errors());
+        let diagnostics = diagnostics.into_vec();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::UnknownDirective);
+        assert_eq!(diagnostics[0].to_string(),
+            "1:1: warning: unknown directive `playscript-monolog-begin`; did you mean `playscript-monologue-begin`?");
+    }
+
+    #[test]
+    fn report_monologues() {
+        let s = r#"A> Hi.
+
+<!-- playscript-monologue-begin -->
+Alone (sighs
+<!-- playscript-monologue-end -->
+
+<!-- playscript-monologue-end -->
+
+<!-- playscript-monologue-begin -->
+Forever."#;
+        assert_eq!(check(s), vec![
+            (4, 7, DiagnosticKind::UnclosedParenthesis),
+            (7, 1, DiagnosticKind::UnmatchedMonologueEnd),
+            (9, 1, DiagnosticKind::UnterminatedMonologue),
+        ]);
+        assert!(lint(s, &Options::default()).has_errors());
+    }
+
+    #[test]
+    fn report_empty_speeches_and_unknown_characters() {
+        let s = "Figaro> Hello.\nFigar>\nSuzanne (exit)>\nBartholo> Hmm.";
+        let characters = vec!["Figaro".to_owned(), "Suzanne".to_owned()];
+        let diagnostics = Lint::new(Options::default())
+            .characters(characters)
+            .run(s)
+            .into_vec();
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec![
+            "2:1: warning: speech of `Figar` is empty",
+            "2:1: error: unknown character `Figar`; did you mean `Figaro`?",
+            "3:1: warning: speech of `Suzanne` is empty",
+            "4:1: error: unknown character `Bartholo`",
+        ]);
+        assert!(check("A> (exit)").is_empty());
+    }
+
+    #[test]
+    fn validate_characters_with_declared_cast() {
+        let s = r#"Suzane> Tiens, Figaro!
+
+<!-- playscript-cast
+Figaro: valet de chambre du comte
+Suzanne: camariste de la comtesse
+-->
+
+Figaro> Dix-neuf pieds sur vingt-six."#;
+        let messages: Vec<String> = lint(s, &Options::default()).into_vec()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(messages, vec![
+            "1:1: error: unknown character `Suzane`; did you mean `Suzanne`?",
+        ]);
+        assert!(lint("<!-- playscript-cast\nFigaro (F)\n-->\n\nF> Hello.", &Options::default()).is_empty());
+    }
+
+    #[test]
+    fn report_aliases_before_declaration() {
+        let s = "F> Hello.\n\n<!-- playscript-cast\nFigaro (F)\n-->\n\nF> Bye.\nFigaro> Bye.";
+        let messages: Vec<String> = lint(s, &Options::default()).into_vec()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(messages, vec![
+            "1:1: error: alias `F` of `Figaro` is used before its declaration",
+        ]);
+    }
+
+    #[test]
+    fn measure_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(suggest("Figar", vec!["Figaro", "Suzanne"]), Some("Figaro"));
+        assert_eq!(suggest("Bartholo", vec!["Figaro", "Suzanne"]), None);
+    }
+}
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::io;
+use pulldown_cmark::Event;
+use pulldown_cmark::escape::escape_html;
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "$CARGO_MANIFEST_DIR/assets/"]
+struct Assets;
+
+/// Style sheets embedded in this crate.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Style {
+    /// `play.css`
+    Default,
+    /// `play_ja.css`
+    Japanese,
+    /// `play_ja_vertical.css` for vertical writing
+    JapaneseVertical,
+}
+
+impl Style {
+    pub fn for_lang(lang: &str) -> Self {
+        if lang == "ja" {
+            Style::Japanese
+        } else {
+            Style::Default
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Style::Default => "play.css",
+            Style::Japanese => "play_ja.css",
+            Style::JapaneseVertical => "play_ja_vertical.css",
+        }
+    }
+
+    /// Returns the content of the embedded style sheet.
+    pub fn content(&self) -> Cow<'static, str> {
+        let data = Assets::get(self.file_name())
+            .expect("style sheet is embedded");
+
+        match data {
+            Cow::Borrowed(bytes) => String::from_utf8_lossy(bytes),
+            Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+}
+
+/// How a style sheet is referred from an HTML document.
+#[derive(Debug,Clone,PartialEq)]
+pub enum StyleSheet {
+    /// No style sheet.
+    None,
+    /// `<link>` element with the URL.
+    Link(String),
+    /// `<style>` element with the content of the embedded style sheet.
+    Inline(Style),
+}
+
+/// A writer of a complete HTML document which wraps the events of `MdPlayScript`.
+#[derive(Debug,Clone)]
+pub struct HtmlDocument {
+    title: String,
+    lang: Option<String>,
+    stylesheet: StyleSheet,
+}
+
+impl HtmlDocument {
+    pub fn new<S: Into<String>>(title: S) -> Self {
+        Self {
+            title: title.into(),
+            lang: None,
+            stylesheet: StyleSheet::Link(Style::Default.file_name().to_owned()),
+        }
+    }
+
+    pub fn lang<S: Into<String>>(self, lang: S) -> Self {
+        Self {
+            lang: Some(lang.into()),
+            ..self
+        }
+    }
+
+    pub fn stylesheet(self, stylesheet: StyleSheet) -> Self {
+        Self {
+            stylesheet,
+            ..self
+        }
+    }
+
+    fn prelude(&self) -> String {
+        let mut s = String::new();
+
+        s += "<!DOCTYPE html>\n";
+        match self.lang.as_ref() {
+            Some(lang) => {
+                s += "<html lang=\"";
+                escape_html(&mut s, lang).unwrap();
+                s += "\">\n";
+            },
+            None => {
+                s += "<html>\n";
+            },
+        }
+        s += "<head>\n";
+        s += "  <meta charset=\"utf-8\" />\n";
+        s += "  <title>";
+        escape_html(&mut s, &self.title).unwrap();
+        s += "</title>\n";
+        match &self.stylesheet {
+            StyleSheet::None => {},
+            StyleSheet::Link(url) => {
+                s += "  <link href=\"";
+                escape_html(&mut s, url).unwrap();
+                s += "\" rel=\"stylesheet\" />\n";
+            },
+            StyleSheet::Inline(style) => {
+                s += "  <style>\n";
+                s += &style.content();
+                s += "  </style>\n";
+            },
+        }
+        s += "</head>\n";
+        s += "<body>\n";
+        s += "<div class=\"play\">\n";
+
+        s
+    }
+
+    fn postlude(&self) -> &'static str {
+        "</div>\n</body>\n</html>\n"
+    }
+
+    pub fn push_html<'a, I>(&self, s: &mut String, iter: I)
+    where
+        I: Iterator<Item=Event<'a>>,
+    {
+        *s += &self.prelude();
+        pulldown_cmark::html::push_html(s, iter);
+        *s += self.postlude();
+    }
+
+    pub fn write_html<'a, I, W>(&self, mut writer: W, iter: I) -> io::Result<()>
+    where
+        I: Iterator<Item=Event<'a>>,
+        W: io::Write,
+    {
+        writer.write_all(self.prelude().as_bytes())?;
+        pulldown_cmark::html::write_html(&mut writer, iter)?;
+        writer.write_all(self.postlude().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use crate::MdPlayScript;
+
+    #[test]
+    fn embedded_style_sheets() {
+        assert!(Style::Default.content().contains(".speech"));
+        assert!(Style::Japanese.content().contains(".speech"));
+        assert!(Style::JapaneseVertical.content().contains("writing-mode: vertical-rl"));
+    }
+
+    #[test]
+    fn document_with_linked_style_sheet() {
+        let doc = HtmlDocument::new("A & B").lang("en");
+        let mut s = String::new();
+        doc.push_html(&mut s, MdPlayScript::new(Parser::new("A> Hello!")));
+        let expected = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>A &amp; B</title>
+  <link href="play.css" rel="stylesheet" />
+</head>
+<body>
+<div class="play">
+<div class="speech"><h5 id="D0"><a class="header" href="#D0"><span class="character">A</span></a></h5><p><span>Hello!</span></p></div>
+</div>
+</body>
+</html>
+"##;
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn document_with_inline_style_sheet() {
+        let doc = HtmlDocument::new("Title")
+            .stylesheet(StyleSheet::Inline(Style::Japanese));
+        let mut buf = Vec::new();
+        doc.write_html(&mut buf, MdPlayScript::new(Parser::new("A> Hello!"))).unwrap();
+        let s = String::from_utf8(buf).unwrap();
+        assert!(s.contains("<style>"));
+        assert!(s.contains(&*Style::Japanese.content()));
+        assert!(!s.contains("<link"));
+    }
+}
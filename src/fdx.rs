@@ -0,0 +1,308 @@
+//! Final Draft (FDX) output of play scripts.
+//!
+//! `push_fdx` writes a `model::Play` as an FDX document, which is a plain XML file.
+//! A speech becomes `Character`, `Parenthetical` and `Dialogue` paragraphs.
+//! Prose paragraphs and directions become `Action` paragraphs and the titles of acts, scenes
+//! and other headings become `Scene Heading` paragraphs.
+use pulldown_cmark::escape::escape_html;
+use crate::model::{Play, Block, Speech, Inline, plain_text};
+
+#[derive(Debug,Clone,Copy,Default,PartialEq)]
+struct TextStyle {
+    bold: bool,
+    italic: bool,
+    strikeout: bool,
+}
+
+impl TextStyle {
+    fn attribute(&self) -> Option<String> {
+        let mut styles = Vec::new();
+
+        if self.bold {
+            styles.push("Bold");
+        }
+        if self.italic {
+            styles.push("Italic");
+        }
+        if self.strikeout {
+            styles.push("Strikeout");
+        }
+
+        if styles.is_empty() {
+            None
+        } else {
+            Some(styles.join("+"))
+        }
+    }
+}
+
+/// A run of text with the same style, which is written as a `<Text>` element.
+#[derive(Debug,Clone,PartialEq)]
+struct Run {
+    style: TextStyle,
+    text: String,
+}
+
+fn push_run(runs: &mut Vec<Run>, style: TextStyle, text: &str) {
+    match runs.last_mut() {
+        Some(run) if run.style == style => run.text.push_str(text),
+        _ => runs.push(Run {
+            style,
+            text: text.to_owned(),
+        }),
+    }
+}
+
+fn collect_runs(inlines: &[Inline], style: TextStyle, runs: &mut Vec<Run>) {
+    for inline in inlines.iter() {
+        match inline {
+            Inline::Text(s) | Inline::Code(s) => push_run(runs, style, s),
+            Inline::Html(_) => {},
+            Inline::SoftBreak | Inline::HardBreak => push_run(runs, style, " "),
+            Inline::Emphasis(v) => collect_runs(v, TextStyle { italic: true, ..style }, runs),
+            Inline::Strong(v) => collect_runs(v, TextStyle { bold: true, ..style }, runs),
+            Inline::Strikethrough(v) => collect_runs(v, TextStyle { strikeout: true, ..style }, runs),
+            Inline::Link { content, .. } | Inline::Image { content, .. } => {
+                collect_runs(content, style, runs);
+            },
+            Inline::Direction(v) => {
+                push_run(runs, style, "(");
+                collect_runs(v, style, runs);
+                push_run(runs, style, ")");
+            },
+        }
+    }
+}
+
+fn to_runs(inlines: &[Inline]) -> Vec<Run> {
+    let mut runs = Vec::new();
+    collect_runs(inlines, TextStyle::default(), &mut runs);
+    runs
+}
+
+fn parenthesize(runs: Vec<Run>) -> Vec<Run> {
+    let mut output = Vec::with_capacity(runs.len() + 2);
+    push_run(&mut output, TextStyle::default(), "(");
+    for run in runs.into_iter() {
+        push_run(&mut output, run.style, &run.text);
+    }
+    push_run(&mut output, TextStyle::default(), ")");
+    output
+}
+
+/// Removes white spaces at the beginning and the end of runs.
+fn trim_runs(runs: &mut Vec<Run>) {
+    if let Some(run) = runs.first_mut() {
+        run.text = run.text.trim_start().to_owned();
+    }
+    if let Some(run) = runs.last_mut() {
+        run.text = run.text.trim_end().to_owned();
+    }
+    runs.retain(|run| !run.text.is_empty());
+}
+
+fn push_paragraph(s: &mut String, indent: &str, attributes: &str, mut runs: Vec<Run>) {
+    trim_runs(&mut runs);
+    if runs.is_empty() {
+        return;
+    }
+
+    *s += indent;
+    *s += "<Paragraph ";
+    *s += attributes;
+    *s += ">";
+    for run in runs.iter() {
+        match run.style.attribute() {
+            Some(style) => {
+                *s += "<Text Style=\"";
+                *s += &style;
+                *s += "\">";
+            },
+            None => *s += "<Text>",
+        }
+        escape_html(&mut *s, &run.text).unwrap();
+        *s += "</Text>";
+    }
+    *s += "</Paragraph>\n";
+}
+
+fn push_content(s: &mut String, paragraph_type: &str, runs: Vec<Run>) {
+    push_paragraph(s, "    ", &format!("Type=\"{}\"", paragraph_type), runs);
+}
+
+fn plain_run(text: &str) -> Vec<Run> {
+    vec![Run {
+        style: TextStyle::default(),
+        text: text.to_owned(),
+    }]
+}
+
+fn push_speech(s: &mut String, speech: &Speech) {
+    push_content(s, "Character", plain_run(&speech.character));
+
+    if !speech.direction.is_empty() {
+        push_content(s, "Parenthetical", parenthesize(to_runs(&speech.direction)));
+    }
+
+    // Splits the body at directions.
+    let mut segments: Vec<(bool, &[Inline])> = Vec::new();
+    let mut start = 0;
+    for (index, inline) in speech.body.iter().enumerate() {
+        if let Inline::Direction(direction) = inline {
+            segments.push((false, &speech.body[start..index]));
+            segments.push((true, direction.as_slice()));
+            start = index + 1;
+        }
+    }
+    segments.push((false, &speech.body[start..]));
+    segments.retain(|(is_direction, inlines)| *is_direction || !plain_text(inlines).trim().is_empty());
+
+    // Directions after the last dialogue are written as actions.
+    let last_dialogue = segments.iter()
+        .rposition(|(is_direction, _)| !is_direction)
+        .map(|pos| pos + 1)
+        .unwrap_or(segments.len());
+
+    for (index, (is_direction, inlines)) in segments.into_iter().enumerate() {
+        if !is_direction {
+            push_content(s, "Dialogue", to_runs(inlines));
+        } else if index < last_dialogue {
+            push_content(s, "Parenthetical", parenthesize(to_runs(inlines)));
+        } else {
+            push_content(s, "Action", to_runs(inlines));
+        }
+    }
+}
+
+fn push_title_page(s: &mut String, play: &Play) {
+    let mut lines = Vec::new();
+
+    if let Some(title) = play.title.as_ref() {
+        lines.push(title.as_str());
+    }
+    if let Some(subtitle) = play.subtitle.as_ref() {
+        lines.push(subtitle.as_str());
+    }
+    if !play.authors.is_empty() {
+        lines.push("Written by");
+        lines.extend(play.authors.iter().map(|author| author.as_str()));
+    }
+
+    if lines.is_empty() {
+        return;
+    }
+
+    *s += "  <TitlePage>\n";
+    *s += "    <Content>\n";
+    for line in lines.into_iter() {
+        push_paragraph(s, "      ", "Alignment=\"Center\" Type=\"Action\"", plain_run(line));
+    }
+    *s += "    </Content>\n";
+    *s += "  </TitlePage>\n";
+}
+
+/// Writes a play as an FDX document.
+pub fn push_fdx(s: &mut String, play: &Play) {
+    *s += "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\" ?>\n";
+    *s += "<FinalDraft DocumentType=\"Script\" Template=\"No\" Version=\"4\">\n";
+    *s += "  <Content>\n";
+
+    for act in play.acts.iter() {
+        if let Some(title) = act.title.as_ref() {
+            push_content(s, "Scene Heading", plain_run(title));
+        }
+
+        for scene in act.scenes.iter() {
+            if let Some(title) = scene.title.as_ref() {
+                push_content(s, "Scene Heading", plain_run(title));
+            }
+
+            for block in scene.blocks.iter() {
+                match block {
+                    Block::Speech(speech) => push_speech(s, speech),
+                    Block::Direction { content } => {
+                        for inline in content.iter() {
+                            if let Inline::Direction(direction) = inline {
+                                push_content(s, "Action", to_runs(direction));
+                            }
+                        }
+                    },
+                    Block::Monologue { content } | Block::Prose { content } => {
+                        push_content(s, "Action", to_runs(content));
+                    },
+                    Block::Heading { content, .. } => {
+                        push_content(s, "Scene Heading", to_runs(content));
+                    },
+                }
+            }
+        }
+    }
+
+    *s += "  </Content>\n";
+    push_title_page(s, play);
+    *s += "</FinalDraft>\n";
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use crate::{Options, Params};
+
+    fn export(s: &str, params: Params) -> String {
+        let play = Play::from_events(Parser::new(s), &Options::default(), params);
+        let mut buf = String::new();
+        push_fdx(&mut buf, &play);
+        buf
+    }
+
+    #[test]
+    fn export_speeches_to_fdx() {
+        let s = r#"### Scène I
+
+Figaro (running)> Sans comparaison, *ma **charmante***. (beat) Oh ! (exit)
+Suzanne> A & B
+
+(Suzanne s'assied.)
+"#;
+        assert_eq!(export(s, Params::default()), r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>
+<FinalDraft DocumentType="Script" Template="No" Version="4">
+  <Content>
+    <Paragraph Type="Scene Heading"><Text>Scène I</Text></Paragraph>
+    <Paragraph Type="Character"><Text>Figaro</Text></Paragraph>
+    <Paragraph Type="Parenthetical"><Text>(running)</Text></Paragraph>
+    <Paragraph Type="Dialogue"><Text>Sans comparaison, </Text><Text Style="Italic">ma </Text><Text Style="Bold+Italic">charmante</Text><Text>.</Text></Paragraph>
+    <Paragraph Type="Parenthetical"><Text>(beat)</Text></Paragraph>
+    <Paragraph Type="Dialogue"><Text>Oh !</Text></Paragraph>
+    <Paragraph Type="Action"><Text>exit</Text></Paragraph>
+    <Paragraph Type="Character"><Text>Suzanne</Text></Paragraph>
+    <Paragraph Type="Dialogue"><Text>A &amp; B</Text></Paragraph>
+    <Paragraph Type="Action"><Text>Suzanne s'assied.</Text></Paragraph>
+  </Content>
+</FinalDraft>
+"#);
+    }
+
+    #[test]
+    fn export_title_page_to_fdx() {
+        let params = Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: Some("Comédie".to_owned()),
+            authors: vec!["Beaumarchais".to_owned()],
+        };
+        let fdx = export("Prose.", params);
+        assert!(fdx.contains(r#"    <Paragraph Type="Action"><Text>Prose.</Text></Paragraph>
+  </Content>
+  <TitlePage>
+    <Content>
+      <Paragraph Alignment="Center" Type="Action"><Text>Le Mariage de Figaro</Text></Paragraph>
+      <Paragraph Alignment="Center" Type="Action"><Text>Comédie</Text></Paragraph>
+      <Paragraph Alignment="Center" Type="Action"><Text>Written by</Text></Paragraph>
+      <Paragraph Alignment="Center" Type="Action"><Text>Beaumarchais</Text></Paragraph>
+    </Content>
+  </TitlePage>
+</FinalDraft>
+"#));
+    }
+}
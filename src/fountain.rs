@@ -0,0 +1,894 @@
+//! Conversion from [Fountain](https://fountain.io/syntax) screenplays.
+//!
+//! `parse_fountain` reads a Fountain text into elements. Character cues and dialogues are
+//! parsed by `parse_heading` and `parse_body`, so that speeches have the same structure as
+//! the ones of play scripts. Names without cased letters, e.g. Japanese names, are character
+//! cues only if they are forced by `@`.
+//! Sections of level n become headings of level n + 1 and scene headings become level 3
+//! headings, which correspond to acts and scenes of `model::Play`.
+//!
+//! `push_fountain` writes a `model::Play` in Fountain. Acts become sections, scenes become
+//! scene headings and speeches become character cues followed by dialogues.
+use pulldown_cmark::{Event, Tag};
+use crate::interface::Params;
+use crate::model::{self, Play, Block};
+use crate::renderer::Renderer;
+use crate::speech::{Speech, Heading, Direction, Inline, parse_heading, parse_body};
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Element {
+    /// A section such as `# Act One`.
+    Section { level: u32, text: String },
+    /// A scene heading such as `INT. HOUSE - DAY`.
+    SceneHeading(String),
+    Speech(Speech<'static>),
+    Action(Vec<Event<'static>>),
+    Transition(String),
+}
+
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Fountain {
+    /// Title, subtitle and authors in the title page.
+    pub params: Params,
+    pub elements: Vec<Element>,
+}
+
+/// Removes notes `[[...]]` and boneyards `/* ... */`.
+fn remove_comments(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+
+    loop {
+        let next = match (rest.find("[["), rest.find("/*")) {
+            (Some(a), Some(b)) if b < a => Some((b, "*/")),
+            (Some(a), _) => Some((a, "]]")),
+            (None, Some(b)) => Some((b, "*/")),
+            (None, None) => None,
+        };
+
+        match next {
+            Some((start, close)) => {
+                output += &rest[..start];
+                match rest[start+2..].find(close) {
+                    Some(end) => rest = &rest[start+2+end+2..],
+                    None => rest = "",
+                }
+            },
+            None => {
+                output += rest;
+                break;
+            },
+        }
+    }
+
+    output
+}
+
+fn find_closing(s: &str, delim: &str) -> Option<usize> {
+    match s.find(delim) {
+        Some(0) | None => None,
+        Some(pos) => Some(pos),
+    }
+}
+
+/// Parses emphases of Fountain: `***bold italic***`, `**bold**`, `*italic*` and `_underline_`.
+/// Underlines are converted into emphases.
+pub fn parse_inline(s: &str) -> Vec<Event<'static>> {
+    let delims: [(&str, &[Tag<'static>]); 4] = [
+        ("***", &[Tag::Strong, Tag::Emphasis]),
+        ("**", &[Tag::Strong]),
+        ("*", &[Tag::Emphasis]),
+        ("_", &[Tag::Emphasis]),
+    ];
+    let mut events = Vec::new();
+    let mut text = String::new();
+    let mut rest = s;
+
+    'outer: while let Some(c) = rest.chars().next() {
+        if c == '\\' {
+            let mut chars = rest.chars();
+            chars.next();
+            if let Some(escaped) = chars.next() {
+                text.push(escaped);
+            }
+            rest = chars.as_str();
+            continue;
+        }
+
+        for (delim, tags) in delims.iter() {
+            if !rest.starts_with(delim) {
+                continue;
+            }
+
+            let after = &rest[delim.len()..];
+            if let Some(end) = find_closing(after, delim) {
+                if !text.is_empty() {
+                    events.push(Event::Text(std::mem::take(&mut text).into()));
+                }
+                for tag in tags.iter() {
+                    events.push(Event::Start(tag.clone()));
+                }
+                events.extend(parse_inline(&after[..end]));
+                for tag in tags.iter().rev() {
+                    events.push(Event::End(tag.clone()));
+                }
+                rest = &after[end+delim.len()..];
+                continue 'outer;
+            }
+        }
+
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if !text.is_empty() {
+        events.push(Event::Text(text.into()));
+    }
+
+    events
+}
+
+fn parse_lines(lines: &[&str]) -> Vec<Event<'static>> {
+    let mut events = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            events.push(Event::SoftBreak);
+        }
+        events.extend(parse_inline(line.trim()));
+    }
+
+    events
+}
+
+fn is_scene_heading(s: &str) -> bool {
+    if s.starts_with('.') {
+        return !s.starts_with("..");
+    }
+
+    let upper = s.to_uppercase();
+    ["INT./EXT", "INT/EXT", "I/E", "INT", "EXT", "EST"].iter()
+        .any(|prefix| {
+            upper.starts_with(prefix) && matches!(upper[prefix.len()..].chars().next(), Some('.') | Some(' '))
+        })
+}
+
+fn is_transition(s: &str) -> bool {
+    if s.starts_with('>') {
+        return !s.ends_with('<');
+    }
+
+    s.ends_with("TO:") && !s.chars().any(|c| c.is_lowercase())
+}
+
+fn is_character(s: &str) -> bool {
+    if s.starts_with('@') {
+        return true;
+    }
+
+    let name = match s.find('(') {
+        Some(pos) => &s[..pos],
+        None => s,
+    };
+
+    // Names without cased letters, e.g. Japanese names, are cues only if forced by `@`.
+    name.chars().any(|c| c.is_uppercase()) && !name.chars().any(|c| c.is_lowercase())
+}
+
+fn is_parenthetical(s: &str) -> bool {
+    s.starts_with('(') && s.ends_with(')')
+}
+
+fn parse_speech_block(cue: &str, lines: &[&str]) -> Speech<'static> {
+    let cue = cue.trim_start_matches('@').trim_end_matches('^').trim();
+    let mut lines = lines;
+
+    let heading = match lines.first().map(|line| line.trim()) {
+        Some(line) if is_parenthetical(line) && !cue.contains('(') => {
+            lines = &lines[1..];
+            parse_heading(&format!("{} {}", cue, line))
+        },
+        _ => parse_heading(cue),
+    };
+
+    let dialogue: Vec<&str> = lines.iter()
+        .map(|line| line.trim().trim_start_matches('~'))
+        .collect();
+
+    Speech {
+        heading,
+        body: parse_body(parse_lines(&dialogue)),
+    }
+}
+
+fn parse_title_page(lines: &[&str], params: &mut Params) {
+    let mut key = String::new();
+
+    for line in lines.iter() {
+        let (value, is_continued) = match line.find(':') {
+            Some(pos) if !line.starts_with(char::is_whitespace) => {
+                key = line[..pos].trim().to_lowercase();
+                (line[pos+1..].trim(), false)
+            },
+            _ => (line.trim(), true),
+        };
+
+        if value.is_empty() {
+            continue;
+        }
+
+        match key.as_str() {
+            "title" => {
+                let title = match params.title.take() {
+                    Some(title) if is_continued => format!("{} {}", title, value),
+                    _ => value.to_owned(),
+                };
+                params.title.replace(title);
+            },
+            "subtitle" => {
+                params.subtitle.replace(value.to_owned());
+            },
+            "author" | "authors" => {
+                params.authors.push(value.to_owned());
+            },
+            _ => {},
+        }
+    }
+}
+
+fn is_title_page(lines: &[&str]) -> bool {
+    match lines.first() {
+        Some(line) => match line.find(':') {
+            Some(pos) => {
+                let key = &line[..pos];
+                !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == ' ')
+            },
+            None => false,
+        },
+        None => false,
+    }
+}
+
+fn parse_block(lines: &[&str], elements: &mut Vec<Element>) {
+    let first = match lines.first() {
+        Some(line) => line.trim(),
+        None => return,
+    };
+    let rest = &lines[1..];
+
+    if first.starts_with('#') {
+        let level = first.chars().take_while(|c| *c == '#').count();
+        elements.push(Element::Section {
+            level: level as u32,
+            text: first[level..].trim().to_owned(),
+        });
+        parse_block(rest, elements);
+    } else if first.starts_with('=') {
+        // page breaks and synopses
+        parse_block(rest, elements);
+    } else if is_scene_heading(first) {
+        let heading = first.strip_prefix('.').unwrap_or(first);
+        let heading = match heading.find('#') {
+            Some(pos) => &heading[..pos],
+            None => heading,
+        };
+        elements.push(Element::SceneHeading(heading.trim().to_owned()));
+        parse_block(rest, elements);
+    } else if lines.len() == 1 && is_transition(first) {
+        let transition = first.trim_start_matches('>').trim();
+        elements.push(Element::Transition(transition.to_owned()));
+    } else if first.starts_with('!') {
+        let mut lines = lines.to_vec();
+        lines[0] = &lines[0].trim_start()[1..];
+        elements.push(Element::Action(parse_lines(&lines)));
+    } else if lines.len() >= 2 && is_character(first) {
+        elements.push(Element::Speech(parse_speech_block(first, rest)));
+    } else {
+        let lines: Vec<&str> = lines.iter()
+            .map(|line| {
+                let line = line.trim();
+                match line.strip_prefix('>').and_then(|s| s.strip_suffix('<')) {
+                    Some(centered) => centered,
+                    None => line,
+                }
+            })
+            .collect();
+        elements.push(Element::Action(parse_lines(&lines)));
+    }
+}
+
+/// Parses a Fountain text.
+pub fn parse_fountain(s: &str) -> Fountain {
+    let text = remove_comments(s);
+    let mut fountain = Fountain::default();
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    let mut blocks = blocks.into_iter().peekable();
+    if let Some(first) = blocks.peek() {
+        if is_title_page(first) {
+            parse_title_page(first, &mut fountain.params);
+            blocks.next();
+        }
+    }
+
+    for block in blocks {
+        parse_block(&block, &mut fountain.elements);
+    }
+
+    fountain
+}
+
+fn push_direction_text<'a>(direction: Direction<'a>, events: &mut Vec<Event<'a>>) {
+    events.push(Event::Text("(".into()));
+    events.extend(direction.0);
+    events.push(Event::Text(")".into()));
+}
+
+fn heading_to_markdown<'a>(heading: Heading<'a>, events: &mut Vec<Event<'a>>) {
+    let mut s = heading.character.to_string();
+
+    if !heading.direction.0.is_empty() {
+        s += " (";
+        for event in heading.direction.0.iter() {
+            if let Event::Text(text) = event {
+                s += text;
+            }
+        }
+        s += ")";
+    }
+    s += "> ";
+
+    events.push(Event::Text(s.into()));
+}
+
+impl Fountain {
+    /// Renders the elements with a renderer.
+    pub fn render<R: Renderer>(self, renderer: &R) -> Vec<Event<'static>> {
+        let mut events = Vec::new();
+
+        for element in self.elements.into_iter() {
+            match element {
+                Element::Section { level, text } => {
+                    let level = std::cmp::min(level + 1, 6);
+                    events.push(Event::Start(Tag::Heading(level)));
+                    events.push(Event::Text(text.into()));
+                    events.push(Event::End(Tag::Heading(level)));
+                },
+                Element::SceneHeading(text) => {
+                    events.push(Event::Start(Tag::Heading(3)));
+                    events.push(Event::Text(text.into()));
+                    events.push(Event::End(Tag::Heading(3)));
+                },
+                Element::Speech(speech) => {
+                    renderer.render_speech(speech, &mut events);
+                    events.push(Event::SoftBreak);
+                },
+                Element::Action(action) => {
+                    renderer.render_paragraph(action, &mut events);
+                },
+                Element::Transition(text) => {
+                    renderer.render_paragraph(vec![Event::Text(text.into())], &mut events);
+                },
+            }
+        }
+
+        events
+    }
+
+    /// Converts the elements into Markdown events of a play script.
+    ///
+    /// Consecutive speeches are put into a paragraph.
+    pub fn to_markdown_events(&self) -> Vec<Event<'static>> {
+        let mut events = Vec::new();
+        let mut in_speeches = false;
+
+        for element in self.elements.iter().cloned() {
+            let is_speech = matches!(element, Element::Speech(_));
+            if in_speeches && !is_speech {
+                events.push(Event::End(Tag::Paragraph));
+            }
+
+            match element {
+                Element::Section { level, text } => {
+                    let level = std::cmp::min(level + 1, 6);
+                    events.push(Event::Start(Tag::Heading(level)));
+                    events.push(Event::Text(text.into()));
+                    events.push(Event::End(Tag::Heading(level)));
+                },
+                Element::SceneHeading(text) => {
+                    events.push(Event::Start(Tag::Heading(3)));
+                    events.push(Event::Text(text.into()));
+                    events.push(Event::End(Tag::Heading(3)));
+                },
+                Element::Speech(speech) => {
+                    if in_speeches {
+                        events.push(Event::SoftBreak);
+                    } else {
+                        events.push(Event::Start(Tag::Paragraph));
+                    }
+                    heading_to_markdown(speech.heading, &mut events);
+                    for inline in speech.body.into_iter() {
+                        match inline {
+                            Inline::Event(event) => events.push(event),
+                            Inline::Direction(direction) => push_direction_text(direction, &mut events),
+                        }
+                    }
+                },
+                Element::Action(action) => {
+                    events.push(Event::Start(Tag::Paragraph));
+                    events.extend(action);
+                    events.push(Event::End(Tag::Paragraph));
+                },
+                Element::Transition(text) => {
+                    events.push(Event::Start(Tag::Paragraph));
+                    events.push(Event::Text(text.into()));
+                    events.push(Event::End(Tag::Paragraph));
+                },
+            }
+
+            in_speeches = is_speech;
+        }
+
+        if in_speeches {
+            events.push(Event::End(Tag::Paragraph));
+        }
+
+        events
+    }
+
+    /// Converts the elements into Markdown of a play script.
+    pub fn to_markdown(&self) -> String {
+        let mut buf = String::new();
+        pulldown_cmark_to_cmark::cmark(self.to_markdown_events().into_iter(), &mut buf, None)
+            .unwrap();
+        buf.push('\n');
+        buf
+    }
+}
+
+/// Escapes characters which are interpreted as emphases in Fountain.
+fn escape_fountain(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn push_inline_fountain(s: &mut String, inline: &model::Inline) {
+    match inline {
+        model::Inline::Text(text) | model::Inline::Code(text) => *s += &escape_fountain(text),
+        model::Inline::Html(_) => {},
+        model::Inline::SoftBreak | model::Inline::HardBreak => s.push('\n'),
+        model::Inline::Emphasis(v) => push_wrapped_fountain(s, "*", v),
+        model::Inline::Strong(v) => push_wrapped_fountain(s, "**", v),
+        model::Inline::Strikethrough(v) => push_inlines_fountain(s, v),
+        model::Inline::Link { content, .. } | model::Inline::Image { content, .. } => {
+            push_inlines_fountain(s, content);
+        },
+        model::Inline::Direction(v) => {
+            s.push('(');
+            push_inlines_fountain(s, v);
+            s.push(')');
+        },
+    }
+}
+
+fn push_inlines_fountain(s: &mut String, inlines: &[model::Inline]) {
+    for inline in inlines.iter() {
+        push_inline_fountain(s, inline);
+    }
+}
+
+fn push_wrapped_fountain(s: &mut String, delim: &str, inlines: &[model::Inline]) {
+    *s += delim;
+    push_inlines_fountain(s, inlines);
+    *s += delim;
+}
+
+/// Converts inline elements into a single line.
+fn inlines_to_line(inlines: &[model::Inline]) -> String {
+    let mut s = String::new();
+    push_inlines_fountain(&mut s, inlines);
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Line {
+    Dialogue(String),
+    Parenthetical(String),
+}
+
+/// Splits a body of a speech into dialogue lines and parentheticals.
+fn dialogue_lines(body: &[model::Inline]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    fn flush(current: &mut String, lines: &mut Vec<Line>) {
+        let line = current.trim();
+        if !line.is_empty() {
+            lines.push(Line::Dialogue(line.to_owned()));
+        }
+        current.clear();
+    }
+
+    for inline in body.iter() {
+        match inline {
+            model::Inline::SoftBreak | model::Inline::HardBreak => {
+                flush(&mut current, &mut lines);
+            },
+            model::Inline::Direction(direction) => {
+                flush(&mut current, &mut lines);
+                lines.push(Line::Parenthetical(inlines_to_line(direction)));
+            },
+            inline => push_inline_fountain(&mut current, inline),
+        }
+    }
+    flush(&mut current, &mut lines);
+
+    lines
+}
+
+/// Returns true if a line of action would be interpreted as another element.
+fn needs_forced_action(line: &str) -> bool {
+    is_scene_heading(line) || is_transition(line) || is_character(line)
+        || line.starts_with(&['#', '=', '~', '!', '@', '.'][..])
+}
+
+fn push_action(s: &mut String, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+
+    if needs_forced_action(&lines[0]) {
+        s.push('!');
+    }
+    for line in lines.iter() {
+        *s += line;
+        s.push('\n');
+    }
+    s.push('\n');
+}
+
+fn push_action_inlines(s: &mut String, inlines: &[model::Inline]) {
+    let mut text = String::new();
+    push_inlines_fountain(&mut text, inlines);
+
+    let lines: Vec<String> = text.lines()
+        .map(|line| line.trim().to_owned())
+        .filter(|line| !line.is_empty())
+        .collect();
+    push_action(s, &lines);
+}
+
+fn push_speech(s: &mut String, speech: &model::Speech) {
+    let cue = speech.character.to_uppercase();
+    // Names without cased letters, e.g. Japanese names, need the forced character cue.
+    if !cue.chars().any(|c| c.is_uppercase()) || cue.contains('(') {
+        s.push('@');
+    }
+    *s += &cue;
+    s.push('\n');
+
+    if !speech.direction.is_empty() {
+        *s += &format!("({})\n", inlines_to_line(&speech.direction));
+    }
+
+    let mut lines = dialogue_lines(&speech.body);
+
+    // Directions after the last dialogue line are written as an action.
+    let mut trailing = Vec::new();
+    if lines.iter().any(|line| matches!(line, Line::Dialogue(_))) {
+        while let Some(Line::Parenthetical(_)) = lines.last() {
+            if let Some(Line::Parenthetical(direction)) = lines.pop() {
+                trailing.insert(0, direction);
+            }
+        }
+    }
+
+    for line in lines.iter() {
+        match line {
+            Line::Dialogue(text) => *s += text,
+            Line::Parenthetical(text) => *s += &format!("({})", text),
+        }
+        s.push('\n');
+    }
+    s.push('\n');
+
+    push_action(s, &trailing);
+}
+
+fn push_title_page(s: &mut String, play: &Play) {
+    let mut has_title_page = false;
+
+    if let Some(title) = play.title.as_ref() {
+        *s += &format!("Title: {}\n", title);
+        has_title_page = true;
+    }
+    if let Some(subtitle) = play.subtitle.as_ref() {
+        *s += &format!("Subtitle: {}\n", subtitle);
+        has_title_page = true;
+    }
+    match play.authors.len() {
+        0 => {},
+        1 => {
+            *s += &format!("Author: {}\n", play.authors[0]);
+            has_title_page = true;
+        },
+        _ => {
+            *s += "Authors:\n";
+            for author in play.authors.iter() {
+                *s += &format!("    {}\n", author);
+            }
+            has_title_page = true;
+        },
+    }
+
+    if has_title_page {
+        s.push('\n');
+    }
+}
+
+/// Writes a play in Fountain.
+///
+/// Acts are written as sections and scenes are written as scene headings.
+/// Directions in the middle of a speech become parentheticals and the ones at the end of
+/// a speech become an action. Monologues are written as actions since Fountain has no
+/// counterpart.
+pub fn push_fountain(s: &mut String, play: &Play) {
+    push_title_page(s, play);
+
+    for act in play.acts.iter() {
+        if let Some(title) = act.title.as_ref() {
+            *s += &format!("# {}\n\n", title);
+        }
+
+        for scene in act.scenes.iter() {
+            if let Some(title) = scene.title.as_ref() {
+                if !is_scene_heading(title) {
+                    s.push('.');
+                }
+                *s += &format!("{}\n\n", title);
+            }
+
+            for block in scene.blocks.iter() {
+                match block {
+                    Block::Speech(speech) => push_speech(s, speech),
+                    Block::Direction { content } => {
+                        let lines: Vec<String> = content.iter()
+                            .filter_map(|inline| match inline {
+                                model::Inline::Direction(v) => Some(inlines_to_line(v)),
+                                _ => None,
+                            })
+                            .collect();
+                        push_action(s, &lines);
+                    },
+                    Block::Monologue { content } | Block::Prose { content } => {
+                        push_action_inlines(s, content);
+                    },
+                    Block::Heading { level, content } => {
+                        let level = std::cmp::max(*level, 2) as usize - 1;
+                        *s += &format!("{} {}\n\n", "#".repeat(level), inlines_to_line(content));
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::renderer::HtmlRenderer;
+    use crate::speech::parse_speech;
+    use crate::interface::Options;
+
+    const SCRIPT: &str = r#"Title: Le Mariage
+    de Figaro
+Author: Beaumarchais
+
+# Acte Premier
+
+INT. CHAMBRE - JOUR
+
+Figaro measures the floor. [[A note]]
+
+FIGARO
+Dix-neuf pieds sur vingt-six.
+
+SUZANNE (O.S.)
+Tiens, Figaro!
+
+FIGARO
+(lui prend les mains)
+Sans comparaison, *ma charmante*.
+(beat)
+Oh !
+
+CUT TO:
+"#;
+
+    #[test]
+    fn parse_title_page_and_elements() {
+        let fountain = parse_fountain(SCRIPT);
+        assert_eq!(fountain.params, Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: None,
+            authors: vec!["Beaumarchais".to_owned()],
+        });
+        assert_eq!(fountain.elements[0], Element::Section { level: 1, text: "Acte Premier".to_owned() });
+        assert_eq!(fountain.elements[1], Element::SceneHeading("INT. CHAMBRE - JOUR".to_owned()));
+        assert_eq!(fountain.elements[2], Element::Action(vec![
+            Event::Text("Figaro measures the floor.".into()),
+        ]));
+        assert_eq!(fountain.elements[6], Element::Transition("CUT TO:".to_owned()));
+        assert_eq!(fountain.elements.len(), 7);
+    }
+
+    #[test]
+    fn parse_speeches_as_play_script() {
+        let fountain = parse_fountain(SCRIPT);
+        let expected = parse_speech(vec![Event::Text("FIGARO> Dix-neuf pieds sur vingt-six.".into())]).unwrap();
+        assert_eq!(fountain.elements[3], Element::Speech(expected));
+
+        let expected = parse_speech(vec![Event::Text("SUZANNE (O.S.)> Tiens, Figaro!".into())]).unwrap();
+        assert_eq!(fountain.elements[4], Element::Speech(expected));
+
+        let expected = parse_speech(vec![
+            Event::Text("FIGARO (lui prend les mains)> Sans comparaison, ".into()),
+            Event::Start(Tag::Emphasis),
+            Event::Text("ma charmante".into()),
+            Event::End(Tag::Emphasis),
+            Event::Text(".".into()),
+            Event::SoftBreak,
+            Event::Text("(beat)".into()),
+            Event::SoftBreak,
+            Event::Text("Oh !".into()),
+        ]).unwrap();
+        assert_eq!(fountain.elements[5], Element::Speech(expected));
+    }
+
+    #[test]
+    fn parse_caseless_action_as_paragraph() {
+        let fountain = parse_fountain("雨が降っている。\n窓の外は暗い。\n\n@お蔦\n貴方。");
+        assert_eq!(fountain.to_markdown(), "雨が降っている。\n窓の外は暗い。\n\nお蔦> 貴方。\n");
+    }
+
+    #[test]
+    fn parse_fountain_emphasis() {
+        assert_eq!(parse_inline("a **b** _c_ \\*d* e*"), vec![
+            Event::Text("a ".into()),
+            Event::Start(Tag::Strong),
+            Event::Text("b".into()),
+            Event::End(Tag::Strong),
+            Event::Text(" ".into()),
+            Event::Start(Tag::Emphasis),
+            Event::Text("c".into()),
+            Event::End(Tag::Emphasis),
+            Event::Text(" *d".into()),
+            Event::Start(Tag::Emphasis),
+            Event::Text(" e".into()),
+            Event::End(Tag::Emphasis),
+        ]);
+    }
+
+    #[test]
+    fn convert_fountain_to_markdown() {
+        let fountain = parse_fountain(SCRIPT);
+        assert_eq!(fountain.to_markdown(), r#"## Acte Premier
+
+### INT. CHAMBRE - JOUR
+
+Figaro measures the floor.
+
+FIGARO> Dix-neuf pieds sur vingt-six.
+SUZANNE (O.S.)> Tiens, Figaro!
+FIGARO (lui prend les mains)> Sans comparaison, *ma charmante*.
+(beat)
+Oh !
+
+CUT TO:
+"#);
+    }
+
+    #[test]
+    fn render_fountain_to_html() {
+        let fountain = parse_fountain("FIGARO\n(running)\nHello!");
+        let mut buf = String::new();
+        pulldown_cmark::html::push_html(&mut buf, fountain.render(&HtmlRenderer::default()).into_iter());
+        assert_eq!(buf, r##"<div class="speech"><h5 id="D0"><a class="header" href="#D0"><span class="character">FIGARO</span><span class="direction">running</span></a></h5><p><span>Hello!</span></p></div>
+"##);
+    }
+
+    fn export(s: &str, params: Params) -> String {
+        let play = Play::from_events(pulldown_cmark::Parser::new(s), &Options::default(), params);
+        let mut buf = String::new();
+        push_fountain(&mut buf, &play);
+        buf
+    }
+
+    #[test]
+    fn export_play_to_fountain() {
+        let s = r#"## Acte Premier
+
+### Scène I
+
+Figaro (lui prend les mains)> Sans comparaison, *ma charmante*. (beat) Oh ! (il sort)
+Suzanne> Bonjour.
+
+(Suzanne s'assied.)
+
+#### Note
+"#;
+        let params = Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: None,
+            authors: vec!["Beaumarchais".to_owned()],
+        };
+        assert_eq!(export(s, params), r#"Title: Le Mariage de Figaro
+Author: Beaumarchais
+
+# Acte Premier
+
+.Scène I
+
+FIGARO
+(lui prend les mains)
+Sans comparaison, *ma charmante*.
+(beat)
+Oh !
+
+il sort
+
+SUZANNE
+Bonjour.
+
+Suzanne s'assied.
+
+### Note
+
+"#);
+    }
+
+    #[test]
+    fn export_forced_elements_to_fountain() {
+        let s = "お蔦> 二十三で、\n\nEXT. is not a scene heading.";
+        assert_eq!(export(s, Params::default()), "@お蔦\n二十三で、\n\n!EXT. is not a scene heading.\n\n");
+    }
+
+    #[test]
+    fn round_trip_through_fountain() {
+        let play = Play::from_events(pulldown_cmark::Parser::new("## Act\n\n### INT. ROOM\n\nA (quietly)> Hello (beat) world"),
+            &Options::default(), Params::default());
+        let mut buf = String::new();
+        push_fountain(&mut buf, &play);
+        let fountain = parse_fountain(&buf);
+        let speech = parse_speech(vec![Event::Text("A (quietly)> Hello".into()), Event::SoftBreak,
+            Event::Text("(beat)".into()), Event::SoftBreak, Event::Text("world".into())]).unwrap();
+        assert_eq!(fountain.elements, vec![
+            Element::Section { level: 1, text: "Act".to_owned() },
+            Element::SceneHeading("INT. ROOM".to_owned()),
+            Element::Speech(speech),
+        ]);
+    }
+}
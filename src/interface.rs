@@ -1,25 +1,33 @@
-use std::collections::VecDeque;
+use std::cell::RefCell;
+use std::collections::{VecDeque, vec_deque};
+use std::iter::Chain;
 use pulldown_cmark::{Event, Tag};
-use crate::parser::{FuseOnParagraphEnd, Speeches};
-use crate::speech::{parse_speech, parse_body};
-use crate::renderer::HtmlRenderer;
+use crate::parser::{Delimiters, FuseOnParagraphEnd, Speeches};
+use crate::speech::{parse_speech_with, parse_body_with};
+use crate::renderer::{HtmlRenderer, Renderer, LineLearning, body_text};
+use crate::anchor::{IdScheme, SpeechIds, SpeechKey};
+use crate::cast::{Cast, CastListEntry};
+use crate::model::Play;
+use crate::stats::{RunningTime, SpeakingRate};
+use crate::structure::{Structure, Unit, heading_text};
+use crate::ruby::strip_ruby;
 
-#[derive(Debug)]
-enum Mode {
+#[derive(Debug,Clone,Copy)]
+pub(crate) enum Mode {
     Nop,
     PlayScript,
     Monologue,
 }
 
 impl Mode {
-    fn is_off(&self) -> bool {
+    pub(crate) fn is_off(&self) -> bool {
         match self {
             Mode::Nop => true,
             _ => false,
         }
     }
 
-    fn is_monologue(&self) -> bool {
+    pub(crate) fn is_monologue(&self) -> bool {
         match self {
             Mode::Monologue => true,
             _ => false,
@@ -27,17 +35,33 @@ impl Mode {
     }
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq)]
 pub struct Options {
-    replace_softbreaks_with: Option<String>,
-    disabled_in_default: bool,
+    pub(crate) replace_softbreaks_with: Option<String>,
+    pub(crate) auto_softbreaks: bool,
+    pub(crate) disabled_in_default: bool,
+    pub(crate) running_time: Option<SpeakingRate>,
+    pub(crate) structure: Structure,
+    pub(crate) id_scheme: IdScheme,
+    pub(crate) line_learning: Option<LineLearning>,
+    pub(crate) ruby: bool,
+    pub(crate) delimiters: Delimiters,
+    pub(crate) vertical: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             replace_softbreaks_with: Some(" ".to_owned()),
+            auto_softbreaks: false,
             disabled_in_default: false,
+            running_time: None,
+            structure: Structure::default(),
+            id_scheme: IdScheme::default(),
+            line_learning: None,
+            ruby: false,
+            delimiters: Delimiters::default(),
+            vertical: false,
         }
     }
 }
@@ -46,12 +70,104 @@ impl Options {
     pub fn default_ja() -> Self {
         Self {
             replace_softbreaks_with: Some("".to_owned()),
+            auto_softbreaks: false,
             disabled_in_default: false,
+            running_time: None,
+            structure: Structure::japanese(),
+            id_scheme: IdScheme::default(),
+            line_learning: None,
+            ruby: true,
+            delimiters: Delimiters::japanese(),
+            vertical: false,
+        }
+    }
+
+    /// Replaces soft breaks with a string, or keeps them if `None` is given.
+    /// It turns off `Options::auto_softbreaks`.
+    pub fn replace_softbreaks_with(self, s: Option<String>) -> Self {
+        Self {
+            replace_softbreaks_with: s,
+            auto_softbreaks: false,
+            ..self
+        }
+    }
+
+    /// Replaces soft breaks by a space only between non-CJK characters, so that Latin words
+    /// quoted in a Japanese play are separated and kana are joined.
+    pub fn auto_softbreaks(self, val: bool) -> Self {
+        Self {
+            auto_softbreaks: val,
+            ..self
+        }
+    }
+
+    pub fn disabled_in_default(self, val: bool) -> Self {
+        Self {
+            disabled_in_default: val,
+            ..self
+        }
+    }
+
+    /// Renders the estimated running time under each scene heading if a rate is given.
+    pub fn running_time(self, rate: Option<SpeakingRate>) -> Self {
+        Self {
+            running_time: rate,
+            ..self
+        }
+    }
+
+    /// Sets the mapping from headings to acts and scenes.
+    pub fn structure(self, structure: Structure) -> Self {
+        Self {
+            structure,
+            ..self
+        }
+    }
+
+    /// Sets the scheme of anchor IDs of speeches rendered by `MdPlayScriptBuilder::build`.
+    pub fn id_scheme(self, scheme: IdScheme) -> Self {
+        Self {
+            id_scheme: scheme,
+            ..self
+        }
+    }
+
+    /// Hides the lines of characters rendered by `MdPlayScriptBuilder::build` for learning.
+    pub fn line_learning(self, learning: Option<LineLearning>) -> Self {
+        Self {
+            line_learning: learning,
+            ..self
+        }
+    }
+
+    /// Renders ruby annotations such as `｜漢字《かんじ》` by `MdPlayScriptBuilder::build`.
+    /// It is enabled by `Options::default_ja`.
+    pub fn ruby(self, val: bool) -> Self {
+        Self {
+            ruby: val,
+            ..self
+        }
+    }
+
+    /// Sets the delimiters of speech headings and directions.
+    /// `Options::default_ja` accepts full-width `＞` and `（`, `）`.
+    pub fn delimiters(self, delimiters: Delimiters) -> Self {
+        Self {
+            delimiters,
+            ..self
+        }
+    }
+
+    /// Renders for vertical writing by `MdPlayScriptBuilder::build`; see the module `vertical`.
+    pub fn vertical(self, val: bool) -> Self {
+        Self {
+            vertical: val,
+            ..self
         }
     }
 }
 
-#[derive(Debug,Default,Clone)]
+#[derive(Debug,Default,Clone,PartialEq)]
 pub struct Params {
     pub title: Option<String>,
     pub subtitle: Option<String>,
@@ -98,11 +214,31 @@ impl MdPlayScriptBuilder {
         where
             I: Iterator<Item=Event<'a>>,
     {
-        let options = self.options.unwrap();
+        let options = self.options.as_ref().unwrap();
         let renderer = HtmlRenderer {
-            replace_softbreak: options.replace_softbreaks_with,
+            replace_softbreak: options.replace_softbreaks_with.clone(),
+            auto_softbreak: options.auto_softbreaks,
+            speech_ids: RefCell::new(SpeechIds::new(options.id_scheme)),
+            line_learning: options.line_learning.clone(),
+            ruby: options.ruby,
+            vertical: options.vertical,
             ..Default::default()
         };
+
+        self.build_with_renderer(iter, renderer)
+    }
+
+    /// Builds `MdPlayScript` with a custom renderer.
+    ///
+    /// `Options::replace_softbreaks_with`, `Options::auto_softbreaks`, `Options::id_scheme`,
+    /// `Options::line_learning`, `Options::ruby` and `Options::vertical` are not applied to
+    /// the renderer.
+    pub fn build_with_renderer<'a, I, R>(self, iter: I, renderer: R) -> MdPlayScript<'a, I, R>
+        where
+            I: Iterator<Item=Event<'a>>,
+            R: Renderer,
+    {
+        let options = self.options.unwrap();
         let mode = if options.disabled_in_default {
             Mode::Nop
         } else {
@@ -110,23 +246,87 @@ impl MdPlayScriptBuilder {
         };
 
         MdPlayScript {
-            iter: Some(iter),
+            iter: Some(Lookahead::new(iter)),
             queue: VecDeque::new(),
             mode: mode,
             params: self.params.unwrap_or(Params::default()),
             renderer: renderer,
             make_title: self.make_title,
+            comments: CommentJoiner::default(),
+            cast: Cast::new(),
+            speeches: Vec::new(),
+            running_time: options.running_time,
+            scene_times: None,
+            structure: options.structure,
+            delimiters: options.delimiters,
+            act: None,
+            scene: None,
+        }
+    }
+}
+
+/// The extensions of Markdown which play scripts are parsed with. They are the ones which
+/// mdbook enables, so that the CLI program, the diagnostics and the preprocessor read the
+/// same document.
+pub fn markdown_options() -> pulldown_cmark::Options {
+    use pulldown_cmark::Options;
+
+    Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+/// An iterator which can read the rest of events ahead.
+struct Lookahead<'a, I> {
+    buffer: VecDeque<Event<'a>>,
+    iter: I,
+}
+
+impl<'a, I> Lookahead<'a, I>
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    fn new(iter: I) -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            iter,
         }
     }
+
+    fn read_to_end(&mut self) -> &VecDeque<Event<'a>> {
+        self.buffer.extend(self.iter.by_ref());
+        &self.buffer
+    }
+}
+
+impl<'a, I> Iterator for Lookahead<'a, I>
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buffer.pop_front().or_else(|| self.iter.next())
+    }
 }
 
-pub struct MdPlayScript<'a, I> {
-    iter: Option<I>,
+pub struct MdPlayScript<'a, I, R = HtmlRenderer> {
+    iter: Option<Lookahead<'a, I>>,
     queue: VecDeque<Event<'a>>,
     mode: Mode,
     params: Params,
-    renderer: HtmlRenderer,
+    renderer: R,
     make_title: Option<Box<dyn FnMut(&Params) -> String>>,
+    comments: CommentJoiner,
+    cast: Cast,
+    /// The characters of the speeches dispatched so far.
+    speeches: Vec<String>,
+    running_time: Option<SpeakingRate>,
+    /// The estimated running times of the scenes which have not begun yet.
+    scene_times: Option<VecDeque<f64>>,
+    structure: Structure,
+    delimiters: Delimiters,
+    /// The titles of the current act and scene.
+    act: Option<String>,
+    scene: Option<String>,
 }
 
 impl<'a, I> MdPlayScript<'a, I>
@@ -134,22 +334,65 @@ where
     I: Iterator<Item=Event<'a>>,
 {
     pub fn new(iter: I) -> Self {
+        Self::with_renderer(iter, Default::default())
+    }
+}
+
+impl<'a, I, R> MdPlayScript<'a, I, R>
+where
+    I: Iterator<Item=Event<'a>>,
+    R: Renderer,
+{
+    pub fn with_renderer(iter: I, renderer: R) -> Self {
         Self {
-            iter: Some(iter),
+            iter: Some(Lookahead::new(iter)),
             queue: VecDeque::new(),
             mode: Mode::PlayScript,
             params: Default::default(),
-            renderer: Default::default(),
+            renderer,
             make_title: None,
+            comments: CommentJoiner::default(),
+            cast: Cast::new(),
+            speeches: Vec::new(),
+            running_time: None,
+            scene_times: None,
+            structure: Structure::default(),
+            delimiters: Delimiters::default(),
+            act: None,
+            scene: None,
         }
     }
 
-    pub fn renderer_mut(&mut self) -> &mut HtmlRenderer {
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut R {
         &mut self.renderer
     }
 
-    pub fn into_inner(self) -> I {
-        self.iter.unwrap()
+    /// Returns the cast declared by `playscript-cast` directives so far.
+    pub fn cast(&self) -> &Cast {
+        &self.cast
+    }
+
+    /// Returns the title of the act which the events read so far belong to.
+    pub fn current_act(&self) -> Option<&str> {
+        self.act.as_deref()
+    }
+
+    /// Returns the title of the scene which the events read so far belong to.
+    pub fn current_scene(&self) -> Option<&str> {
+        self.scene.as_deref()
+    }
+
+    /// Returns the rest of events which have not been dispatched.
+    ///
+    /// Events which have been read ahead, e.g. for `playscript-cast-list`, come first and are
+    /// followed by the inner iterator.
+    pub fn into_inner(self) -> Chain<vec_deque::IntoIter<Event<'a>>, I> {
+        let iter = self.iter.unwrap();
+        iter.buffer.into_iter().chain(iter.iter)
     }
 
     fn dispatch_directive(&mut self, s: &str) {
@@ -167,13 +410,19 @@ where
                 self.mode = Mode::Nop;
             },
             Some(Directive::Title) => {
-                emit_title(&self.params, &mut self.queue);
+                let mut events = Vec::new();
+                self.renderer.render_title(&self.params, &mut events);
+                self.append_events(events);
             },
             Some(Directive::SubTitle) => {
-                emit_subtitle(&self.params, &mut self.queue);
+                let mut events = Vec::new();
+                self.renderer.render_subtitle(&self.params, &mut events);
+                self.append_events(events);
             },
             Some(Directive::Authors) => {
-                emit_authors(&self.params, &mut self.queue);
+                let mut events = Vec::new();
+                self.renderer.render_authors(&self.params, &mut events);
+                self.append_events(events);
             },
             Some(Directive::MakeTitle) => {
                 if let Some(make_title) = self.make_title.as_mut() {
@@ -181,40 +430,144 @@ where
                     self.queue.push_back(Event::Html(cover.into()));
                 }
             },
+            Some(Directive::Cast(cast)) => {
+                self.cast.extend(cast);
+            },
+            Some(Directive::CastList) => {
+                let entries = self.cast_list();
+                let mut events = Vec::new();
+                self.renderer.render_cast_list(&entries, &mut events);
+                self.append_events(events);
+            },
+            None => {},
+        }
+    }
+
+    /// Makes the list of characters in order of their first speeches.
+    ///
+    /// The rest of the document is read ahead to find the speeches after the directive.
+    fn cast_list(&mut self) -> Vec<CastListEntry> {
+        let mut cast = self.cast.clone();
+        let events = self.iter.as_mut().unwrap().read_to_end();
+        let mut scanner = Scanner {
+            mode: self.mode,
+            delimiters: &self.delimiters,
+            structure: &self.structure,
+            act: self.act.clone(),
+            scene: self.scene.clone(),
+        };
+        let ahead = scanner.scan_speeches(events, &mut cast);
+        self.renderer.read_ahead(&ahead);
+
+        let mut entries: Vec<CastListEntry> = Vec::new();
+        let characters = ahead.iter().map(|speech| &speech.character);
+        for (index, character) in self.speeches.iter().chain(characters).enumerate() {
+            if entries.iter().any(|entry| entry.name == *character) {
+                continue;
+            }
+            entries.push(CastListEntry {
+                name: character.clone(),
+                description: cast.get(character).and_then(|member| member.description.clone()),
+                first_speech: index,
+            });
+        }
+
+        entries
+    }
+
+    /// Passes a heading through and moves to the act or the scene which it begins.
+    fn dispatch_heading(&mut self, heading: Vec<Event<'a>>) {
+        let level = match heading.first() {
+            Some(Event::Start(Tag::Heading(level))) => *level,
+            _ => unreachable!(),
+        };
+        let title = heading_text(heading.iter());
+        let unit = self.structure.unit(level, &title);
+        if let (Some(Unit::Scene), Some(rate), None) = (unit, self.running_time, &self.scene_times) {
+            self.estimate_scene_times(&heading, &rate);
+        }
+        self.append_events(heading);
+
+        match unit {
+            Some(Unit::Act) => {
+                self.act = Some(title);
+                self.scene = None;
+                self.renderer.enter_scene(self.act.as_deref(), None);
+            },
+            Some(Unit::Scene) => {
+                self.scene = Some(title);
+                self.renderer.enter_scene(self.act.as_deref(), self.scene.as_deref());
+                if let Some(times) = self.scene_times.as_mut() {
+                    let minutes = times.pop_front().unwrap_or_default();
+                    let mut events = Vec::new();
+                    self.renderer.render_running_time(minutes, &mut events);
+                    self.append_events(events);
+                }
+            },
             None => {},
         }
     }
 
+    /// Estimates the running times of the scene which begins at the heading and the following
+    /// scenes at once.
+    fn estimate_scene_times(&mut self, heading: &[Event<'a>], rate: &SpeakingRate) {
+        let events = self.iter.as_mut().unwrap().read_to_end();
+        let rest: Vec<Event<'a>> = heading.iter()
+            .chain(events.iter())
+            .cloned()
+            .collect();
+        let play = Play::from_events_in_mode(rest.into_iter(), Params::default(), &self.structure, &self.delimiters, self.mode, self.cast.clone());
+        let time = RunningTime::estimate(&play, rate);
+
+        // Scenes without titles hold the events before the first scene heading of each act.
+        self.scene_times = Some(time.scenes.into_iter()
+            .filter(|scene| scene.scene.is_some())
+            .map(|scene| scene.minutes)
+            .collect());
+    }
+
     fn append_events(&mut self, events: Vec<Event<'a>>) {
         self.queue.extend(events);
     }
 
     fn dispatch_speech(&mut self, speech: Vec<Event<'a>>) {
-        match parse_speech(speech) {
-            Ok(speech) => {
+        match parse_speech_with(speech, &self.delimiters) {
+            Ok(mut speech) => {
+                // Ruby annotations of the character are kept unless an alias is resolved.
+                let character = strip_ruby(&speech.heading.character);
+                match self.cast.resolve(&character) {
+                    Some(name) if name != character => {
+                        speech.heading.character = name.to_owned().into();
+                        self.speeches.push(name.to_owned());
+                    },
+                    _ => {
+                        self.speeches.push(character);
+                    },
+                }
                 let mut html = Vec::new();
                 self.renderer.render_speech(speech, &mut html);
                 html.push(Event::SoftBreak);
                 self.append_events(html);
             },
             Err(para) if self.mode.is_monologue() => {
-                let monologue = parse_body(para);
+                let monologue = parse_body_with(para, &self.delimiters);
                 let mut html = Vec::new();
                 self.renderer.render_monologue(monologue, &mut html);
                 self.append_events(html);
             },
             Err(para) => {
                 let mut output = Vec::new();
-                self.renderer.render_events(para, &mut output);
-                self.append_events(wrap_by_paragraph_tag(output));
+                self.renderer.render_paragraph(para, &mut output);
+                self.append_events(output);
             },
         };
     }
 }
 
-impl<'a, I: 'a> Iterator for MdPlayScript<'a, I>
+impl<'a, I: 'a, R> Iterator for MdPlayScript<'a, I, R>
 where
     I: Iterator<Item=Event<'a>>,
+    R: Renderer,
 {
     type Item = Event<'a>;
 
@@ -227,11 +580,16 @@ where
 
         match iter.next() {
             Some(Event::Html(s)) => {
-                self.dispatch_directive(&s);
+                // Directives may read the rest of events ahead.
+                self.iter.replace(iter);
+                if let Some(html) = self.comments.push(&s) {
+                    self.dispatch_directive(&html);
+                }
                 self.queue.push_back(Event::Html(s));
+                iter = self.iter.take().unwrap();
             },
             Some(Event::Start(Tag::Paragraph)) if !self.mode.is_off() => {
-                let mut speeches = Speeches::new(FuseOnParagraphEnd::new(iter));
+                let mut speeches = Speeches::with_delimiters(FuseOnParagraphEnd::new(iter), self.delimiters);
 
                 while let Some(speech) = speeches.next() {
                     self.dispatch_speech(speech);
@@ -239,6 +597,20 @@ where
 
                 iter = speeches.into_inner().into_inner();
             },
+            Some(Event::Start(Tag::Heading(level))) => {
+                let mut heading = vec![Event::Start(Tag::Heading(level))];
+                for event in iter.by_ref() {
+                    let end = matches!(event, Event::End(Tag::Heading(_)));
+                    heading.push(event);
+                    if end {
+                        break;
+                    }
+                }
+                // Scene headings may read the rest of events ahead.
+                self.iter.replace(iter);
+                self.dispatch_heading(heading);
+                iter = self.iter.take().unwrap();
+            },
             Some(event) => {
                 self.queue.push_back(event);
             },
@@ -251,26 +623,86 @@ where
     }
 }
 
-fn wrap_events_by<'a, 'b: 'a>(mut events: Vec<Event<'a>>, start: Event<'b>, end: Event<'b>) -> Vec<Event<'a>> {
-    let mut output = Vec::with_capacity(events.len() + 2);
+/// The state of `MdPlayScript` which is followed in events read ahead.
+struct Scanner<'s> {
+    mode: Mode,
+    delimiters: &'s Delimiters,
+    structure: &'s Structure,
+    act: Option<String>,
+    scene: Option<String>,
+}
 
-    output.push(start);
-    output.append(&mut events);
-    output.push(end);
+impl<'s> Scanner<'s> {
+    /// Returns the speeches in events in the same way as `MdPlayScript` reads them.
+    ///
+    /// The cast is extended by the declarations in the events.
+    fn scan_speeches<'a>(&mut self, events: &VecDeque<Event<'a>>, cast: &mut Cast) -> Vec<SpeechKey> {
+        let mut comments = CommentJoiner::default();
+        let mut keys = Vec::new();
+        let mut iter = events.iter().cloned();
 
-    output
-}
+        while let Some(event) = iter.next() {
+            match event {
+                Event::Html(s) => {
+                    match comments.push(&s).and_then(|html| parse_directive(&html)) {
+                        Some(Directive::MonologueBegin) => {
+                            self.mode = Mode::Monologue;
+                        },
+                        Some(Directive::MonologueEnd) | Some(Directive::PlayScriptOn) => {
+                            self.mode = Mode::PlayScript;
+                        },
+                        Some(Directive::PlayScriptOff) => {
+                            self.mode = Mode::Nop;
+                        },
+                        Some(Directive::Cast(declared)) => {
+                            cast.extend(declared);
+                        },
+                        _ => {},
+                    }
+                },
+                Event::Start(Tag::Paragraph) if !self.mode.is_off() => {
+                    let mut speeches = Speeches::with_delimiters(FuseOnParagraphEnd::new(iter), *self.delimiters);
+
+                    for speech in speeches.by_ref() {
+                        if let Ok(speech) = parse_speech_with(speech, self.delimiters) {
+                            let character = strip_ruby(&speech.heading.character);
+                            keys.push(SpeechKey {
+                                act: self.act.clone(),
+                                scene: self.scene.clone(),
+                                character: cast.resolve(&character).unwrap_or(character.as_str()).to_owned(),
+                                text: body_text(&speech.body),
+                            });
+                        }
+                    }
+
+                    iter = speeches.into_inner().into_inner();
+                },
+                Event::Start(Tag::Heading(level)) => {
+                    let heading: Vec<Event> = iter.by_ref()
+                        .take_while(|event| !matches!(event, Event::End(Tag::Heading(_))))
+                        .collect();
+                    let title = heading_text(heading.iter());
+                    match self.structure.unit(level, &title) {
+                        Some(Unit::Act) => {
+                            self.act = Some(title);
+                            self.scene = None;
+                        },
+                        Some(Unit::Scene) => {
+                            self.scene = Some(title);
+                        },
+                        None => {},
+                    }
+                },
+                _ => {},
+            }
+        }
 
-fn wrap_by_paragraph_tag<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
-    wrap_events_by(
-        events,
-        Event::Start(Tag::Paragraph),
-        Event::End(Tag::Paragraph),
-    )
+        keys
+    }
 }
 
 #[derive(Debug,Clone,PartialEq)]
-enum Directive {
+pub(crate) enum Directive {
     MonologueBegin,
     MonologueEnd,
     PlayScriptOn,
@@ -279,14 +711,22 @@ enum Directive {
     SubTitle,
     Authors,
     MakeTitle,
+    Cast(Cast),
+    CastList,
 }
 
-fn parse_directive(s: &str) -> Option<Directive> {
+pub(crate) fn parse_directive(s: &str) -> Option<Directive> {
     let s = s.trim()
         .strip_prefix("<!--")?
         .strip_suffix("-->")?
         .trim();
 
+    if let Some(rest) = s.strip_prefix("playscript-cast") {
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            return Some(Directive::Cast(Cast::parse(rest)));
+        }
+    }
+
     match s {
         "playscript-monologue-begin" => Some(Directive::MonologueBegin),
         "playscript-monologue-end" => Some(Directive::MonologueEnd),
@@ -296,51 +736,40 @@ fn parse_directive(s: &str) -> Option<Directive> {
         "playscript-subtitle" => Some(Directive::SubTitle),
         "playscript-authors" => Some(Directive::Authors),
         "playscript-make-title" => Some(Directive::MakeTitle),
+        "playscript-cast-list" => Some(Directive::CastList),
         _ => None,
     }
 }
 
-fn emit_title<'a>(params: &Params, queue: &mut VecDeque<Event<'a>>) {
-    let p_start = "<h1 class=\"cover-title\">";
-    let p_end = "</h1>";
-
-    if let Some(content) = params.title.as_ref().cloned() {
-        queue.push_back(Event::Html(p_start.into()));
-        queue.push_back(Event::Text(content.into()));
-        queue.push_back(Event::Html(p_end.into()));
-    }
-}
-
-fn emit_subtitle<'a>(params: &Params, queue: &mut VecDeque<Event<'a>>) {
-    let p_start = "<h2 class=\"cover-title\">";
-    let p_end = "</h2>";
-
-    if let Some(content) = params.subtitle.as_ref().cloned() {
-        queue.push_back(Event::Html(p_start.into()));
-        queue.push_back(Event::Text(content.into()));
-        queue.push_back(Event::Html(p_end.into()));
-    }
+/// Joins the lines of an HTML comment, which are given as separate events.
+#[derive(Debug,Default)]
+pub(crate) struct CommentJoiner {
+    buffer: Option<String>,
 }
 
-fn emit_authors<'a>(params: &Params, queue: &mut VecDeque<Event<'a>>) {
-    let div_start = "<div class=\"authors\">";
-    let div_end = "</div>";
-    let p_start = "<p class=\"cover-author\">";
-    let p_end = "</p>";
-
-    if params.authors.is_empty() {
-        return;
-    }
-
-    queue.push_back(Event::Html(div_start.into()));
-
-    for author in params.authors.iter().cloned() {
-        queue.push_back(Event::Html(p_start.into()));
-        queue.push_back(Event::Text(author.into()));
-        queue.push_back(Event::Html(p_end.into()));
+impl CommentJoiner {
+    /// Returns the whole HTML when a comment is completed or the HTML is not a comment.
+    pub(crate) fn push(&mut self, s: &str) -> Option<String> {
+        match self.buffer.as_mut() {
+            Some(buffer) => {
+                buffer.push_str(s);
+                if s.contains("-->") {
+                    self.buffer.take()
+                } else {
+                    None
+                }
+            },
+            None => {
+                let trimmed = s.trim_start();
+                if trimmed.starts_with("<!--") && !trimmed.contains("-->") {
+                    self.buffer.replace(s.to_owned());
+                    None
+                } else {
+                    Some(s.to_owned())
+                }
+            },
+        }
     }
-
-    queue.push_back(Event::Html(div_end.into()));
 }
 
 #[cfg(test)]
@@ -348,6 +777,7 @@ mod test {
     use super::*;
     use pulldown_cmark::Parser;
     use pulldown_cmark::html::push_html;
+    use crate::speech::{Speech, Inline};
 
     #[test]
     fn consume() {
@@ -379,6 +809,56 @@ A> ...."#;
         eprintln!("{}", buf);
     }
 
+    struct PlainRenderer;
+
+    impl Renderer for PlainRenderer {
+        fn render_speech<'a>(&self, speech: Speech<'a>, events: &mut Vec<Event<'a>>) {
+            events.push(Event::Start(Tag::Paragraph));
+            events.push(Event::Text(speech.heading.character));
+            events.push(Event::Text(": ".into()));
+            for inline in speech.body.into_iter() {
+                if let Inline::Event(event) = inline {
+                    events.push(event);
+                }
+            }
+            events.push(Event::End(Tag::Paragraph));
+        }
+
+        fn render_monologue<'a>(&self, _body: Vec<Inline<'a>>, _events: &mut Vec<Event<'a>>) {
+        }
+
+        fn render_paragraph<'a>(&self, events: Vec<Event<'a>>, output: &mut Vec<Event<'a>>) {
+            output.push(Event::Start(Tag::Paragraph));
+            output.extend(events);
+            output.push(Event::End(Tag::Paragraph));
+        }
+    }
+
+    #[test]
+    fn render_with_custom_renderer() {
+        let s = r#"<!-- playscript-title -->
+
+A> Hello! (waves)
+
+Normal line."#;
+        let params = Params {
+            title: Some("Title".to_owned()),
+            ..Default::default()
+        };
+        let parser = MdPlayScriptBuilder::new()
+            .options(Options::default())
+            .params(params)
+            .build_with_renderer(Parser::new(s), PlainRenderer);
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert_eq!(buf, r#"<h1>Title</h1>
+<!-- playscript-title -->
+<p>A: Hello! </p>
+
+<p>Normal line.</p>
+"#);
+    }
+
     #[test]
     fn parse_correct_directives() {
         assert_eq!(
@@ -402,5 +882,183 @@ A> ...."#;
         assert_eq!(
             parse_directive("<!-- playscript-authors -->"),
             Some(Directive::Authors));
+        assert_eq!(
+            parse_directive("<!-- playscript-cast\nA: a servant\nB\n-->"),
+            Some(Directive::Cast(Cast::parse("A: a servant\nB"))));
+        assert_eq!(
+            parse_directive("<!-- playscript-cast-list -->"),
+            Some(Directive::CastList));
+        assert_eq!(parse_directive("<!-- playscript-castle -->"), None);
+    }
+
+    #[test]
+    fn collect_multi_line_cast_declaration() {
+        let s = "<!-- playscript-cast\nFigaro: valet\nSuzanne\n-->\n\nFigaro> Hello!";
+        let mut parser = MdPlayScript::new(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser.by_ref());
+        assert!(buf.starts_with("<!-- playscript-cast\nFigaro: valet\nSuzanne\n-->\n"));
+        assert_eq!(parser.cast().names().collect::<Vec<_>>(), vec!["Figaro", "Suzanne"]);
+    }
+
+    #[test]
+    fn resolve_character_aliases() {
+        let s = "<!-- playscript-cast\nFigaro (F)\n-->\n\nF> Hello!";
+        let mut buf = String::new();
+        push_html(&mut buf, MdPlayScript::new(Parser::new(s)));
+        assert!(buf.contains(r#"<span class="character">Figaro</span>"#));
+    }
+
+    #[test]
+    fn generate_cast_list() {
+        let s = r#"<!-- playscript-cast
+Figaro (F): valet
+-->
+
+Suzanne> Bonjour.
+
+<!-- playscript-cast-list -->
+
+F> Dix-neuf pieds.
+Suzanne> Quoi ?
+Le Comte> Vingt-six.
+
+<!-- playscript-cast
+Le Comte: Almaviva
+-->
+"#;
+        let mut buf = String::new();
+        push_html(&mut buf, MdPlayScript::new(Parser::new(s)));
+        assert!(buf.contains(concat!(
+            r##"<ul class="cast-list">"##,
+            r##"<li><a class="header" href="#D0"><span class="character">Suzanne</span></a></li>"##,
+            r##"<li><a class="header" href="#D1"><span class="character">Figaro</span></a><span class="description">valet</span></li>"##,
+            r##"<li><a class="header" href="#D3"><span class="character">Le Comte</span></a><span class="description">Almaviva</span></li>"##,
+            r##"</ul>"##)));
+        assert!(buf.contains(r##"<h5 id="D3"><a class="header" href="#D3"><span class="character">Le Comte</span>"##));
+    }
+
+    #[test]
+    fn render_running_time_of_scenes() {
+        let s = "### Scene 1\n\nA> One two three.\n\n### Scene 2\n\nB> Four.";
+        let options = Options::default()
+            .running_time(Some(SpeakingRate::new().words_per_minute(1.0)));
+        let parser = MdPlayScriptBuilder::new()
+            .options(options)
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert!(buf.starts_with("<h3>Scene 1</h3>\n<p class=\"running-time\">3 min</p>"));
+        assert!(buf.contains("<h3>Scene 2</h3>\n<p class=\"running-time\">1 min</p>"));
+    }
+
+    #[test]
+    fn render_running_time_of_scenes_in_acts() {
+        let s = "## Act 1\n\nA> Prologue.\n\n### Scene 1\n\nA> One two three.\n\n## Act 2\n\n### Scene 1\n\nB> Four.";
+        let options = Options::default()
+            .running_time(Some(SpeakingRate::new().words_per_minute(1.0)));
+        let parser = MdPlayScriptBuilder::new()
+            .options(options)
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        let times: Vec<&str> = buf.match_indices("<p class=\"running-time\">")
+            .map(|(pos, _)| &buf[pos..pos + buf[pos..].find("</p>").unwrap()])
+            .collect();
+        assert_eq!(times, vec![
+            "<p class=\"running-time\">3 min",
+            "<p class=\"running-time\">1 min",
+        ]);
+    }
+
+    #[test]
+    fn return_events_read_ahead() {
+        let s = "<!-- playscript-cast-list -->\n\nA> Hello.\n\nB> Hi.";
+        let mut parser = MdPlayScript::new(Parser::new(s));
+        assert!(parser.next().is_some());
+        let rest: Vec<_> = parser.into_inner().collect();
+        assert_eq!(rest, Parser::new(s).skip(1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn track_acts_and_scenes() {
+        let s = "## Acte Premier\n\n### Scène I\n\nFigaro> Dix-neuf pieds.\n\n## Acte II";
+        let mut parser = MdPlayScript::new(Parser::new(s));
+        let mut positions = Vec::new();
+        while let Some(event) = parser.next() {
+            if let Event::Text(text) = event {
+                if text.as_ref() == "Figaro" {
+                    positions.push((parser.current_act().map(str::to_owned), parser.current_scene().map(str::to_owned)));
+                }
+            }
+        }
+        assert_eq!(positions, vec![(Some("Acte Premier".to_owned()), Some("Scène I".to_owned()))]);
+        assert_eq!(parser.current_act(), Some("Acte II"));
+        assert_eq!(parser.current_scene(), None);
+    }
+
+    #[test]
+    fn link_cast_list_to_scene_ids() {
+        let s = "<!-- playscript-cast-list -->\n\n### Scene 1\n\nA> Hello.\n\nB> Hi.\n\n### Scene 2\n\nA> Bye.";
+        let options = Options::default().id_scheme(IdScheme::Scene);
+        let parser = MdPlayScriptBuilder::new()
+            .options(options)
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert!(buf.contains(r##"<li><a class="header" href="#D-scene-1-0">"##));
+        assert!(buf.contains(r##"<li><a class="header" href="#D-scene-1-1">"##));
+        assert!(buf.contains(r##"<h5 id="D-scene-1-0"><a class="header" href="#D-scene-1-0">"##));
+        assert!(buf.contains(r#"<h5 id="D-scene-1-1">"#));
+        assert!(buf.contains(r#"<h5 id="D-scene-2-0">"#));
+    }
+
+    #[test]
+    fn render_ruby_in_japanese_speeches() {
+        let s = "<!-- playscript-cast\nお蔦\n-->\n\nお{蔦|つた}> 可厭《いや》だ。(｜彳《たたず》む)";
+        let mut parser = MdPlayScriptBuilder::new()
+            .options(Options::default_ja())
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser.by_ref());
+        assert!(buf.contains(r#"<span class="character">お<ruby><rb>蔦</rb><rt>つた</rt></ruby></span>"#));
+        assert!(buf.contains("<span><ruby><rb>可厭</rb><rt>いや</rt></ruby>だ。</span>"));
+        assert!(buf.contains(r#"<span class="direction"><ruby><rb>彳</rb><rt>たたず</rt></ruby>む</span>"#));
+        assert_eq!(parser.speeches, vec!["お蔦".to_owned()]);
+    }
+
+    #[test]
+    fn accept_full_width_delimiters() {
+        let s = "お蔦（振返って）＞ 貴方。〔間〕";
+        let delimiters = Delimiters {
+            tortoise_shell: true,
+            preserve_brackets: true,
+            ..Delimiters::japanese()
+        };
+        let parser = MdPlayScriptBuilder::new()
+            .options(Options::default_ja().delimiters(delimiters))
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert!(buf.contains(r#"<span class="character">お蔦</span><span class="direction bracketed">（振返って）</span>"#));
+        assert!(buf.contains(r#"<span>貴方。</span><span class="direction bracketed">〔間〕</span>"#));
+
+        let parser = MdPlayScriptBuilder::new()
+            .options(Options::default())
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert!(!buf.contains("<h5"));
+    }
+
+    #[test]
+    fn join_lines_of_mixed_scripts() {
+        let s = "主税> 先生は\nこう言った。Je ne\nsais pas.";
+        let parser = MdPlayScriptBuilder::new()
+            .options(Options::default_ja().auto_softbreaks(true))
+            .build(Parser::new(s));
+        let mut buf = String::new();
+        push_html(&mut buf, parser);
+        assert!(buf.contains("<span>先生はこう言った。Je ne sais pas.</span>"));
     }
 }
@@ -0,0 +1,436 @@
+//! LaTeX output of play scripts.
+//!
+//! `LatexRenderer` renders speeches into the macros defined in `LATEX_MACROS`.
+//! The raw LaTeX code is carried by `Event::Html` marked as `RawLatex` and the other events
+//! are converted by `push_latex`. HTML in the source is dropped from the output.
+use pulldown_cmark::{Event, Tag};
+use crate::interface::Params;
+use crate::renderer::Renderer;
+use crate::speech::{Speech, Direction, Inline};
+
+/// Definitions of the macros and the environments used by `LatexRenderer`.
+pub const LATEX_MACROS: &str = r#"\newcommand{\character}[1]{\textsc{#1}}
+\newcommand{\stagedirection}[1]{\textit{(#1)}}
+\newenvironment{speech}{\par\noindent\hangindent=2em}{\par\smallskip}
+\newenvironment{monologue}{\par\begin{quote}}{\end{quote}}
+"#;
+
+/// The mark put at the beginning of raw LaTeX code in `Event::Html`.
+const RAW_LATEX_MARK: char = '\u{F8FF}';
+
+/// Raw LaTeX code, which is carried by `Event::Html` with a mark to tell it from HTML.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct RawLatex<'s>(pub &'s str);
+
+impl<'s> RawLatex<'s> {
+    pub fn into_event<'a>(self) -> Event<'a> {
+        Event::Html(format!("{}{}", RAW_LATEX_MARK, self.0).into())
+    }
+
+    /// Returns the LaTeX code if the HTML is marked as raw LaTeX.
+    pub fn from_html(html: &'s str) -> Option<Self> {
+        html.strip_prefix(RAW_LATEX_MARK).map(RawLatex)
+    }
+}
+
+/// Escapes special characters of LaTeX.
+pub fn escape_latex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '\\' => escaped += r"\textbackslash{}",
+            '{' | '}' | '$' | '&' | '%' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '~' => escaped += r"\textasciitilde{}",
+            '^' => escaped += r"\textasciicircum{}",
+            '<' => escaped += r"\textless{}",
+            '>' => escaped += r"\textgreater{}",
+            '|' => escaped += r"\textbar{}",
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Escapes the characters of a URL which are special in the argument of `\href`.
+fn escape_url(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if matches!(c, '\\' | '#' | '%') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[derive(Debug,Default)]
+pub struct LatexRenderer;
+
+impl LatexRenderer {
+    pub fn new() -> Self {
+        LatexRenderer
+    }
+
+    fn raw<'a>(s: &str, events: &mut Vec<Event<'a>>) {
+        events.push(RawLatex(s).into_event());
+    }
+
+    pub fn render_direction<'a>(&self, direction: Direction<'a>, events: &mut Vec<Event<'a>>) {
+        let direction = direction.0;
+        let len = direction.len();
+
+        if len == 0 {
+            return;
+        }
+
+        Self::raw(r"\stagedirection{", events);
+
+        for (index, event) in direction.into_iter().enumerate() {
+            match event {
+                Event::Text(s) => {
+                    let mut s: &str = s.as_ref();
+                    if index == 0 {
+                        s = s.trim_start();
+                    }
+                    if index + 1 == len {
+                        s = s.trim_end();
+                    }
+                    events.push(Event::Text(s.to_owned().into()));
+                },
+                event => {
+                    events.push(event);
+                },
+            }
+        }
+
+        Self::raw("}", events);
+    }
+
+    pub fn render_body<'a>(&self, body: Vec<Inline<'a>>, events: &mut Vec<Event<'a>>) {
+        for inline in body.into_iter() {
+            match inline {
+                Inline::Event(event) => {
+                    events.push(event);
+                },
+                Inline::Direction(direction) => {
+                    self.render_direction(direction, events);
+                },
+            }
+        }
+    }
+}
+
+impl Renderer for LatexRenderer {
+    fn render_speech<'a>(&self, speech: Speech<'a>, events: &mut Vec<Event<'a>>) {
+        Self::raw("\\begin{speech}\n", events);
+        Self::raw(r"\character{", events);
+        events.push(Event::Text(speech.heading.character));
+        Self::raw("}", events);
+        if !speech.heading.direction.0.is_empty() {
+            Self::raw(" ", events);
+            self.render_direction(speech.heading.direction, events);
+        }
+        Self::raw(" ", events);
+        self.render_body(speech.body, events);
+        Self::raw("\n\\end{speech}", events);
+    }
+
+    fn render_monologue<'a>(&self, body: Vec<Inline<'a>>, events: &mut Vec<Event<'a>>) {
+        Self::raw("\\begin{monologue}\n", events);
+        self.render_body(body, events);
+        Self::raw("\n\\end{monologue}\n\n", events);
+    }
+
+    fn render_paragraph<'a>(&self, events: Vec<Event<'a>>, output: &mut Vec<Event<'a>>) {
+        output.push(Event::Start(Tag::Paragraph));
+        output.extend(events);
+        output.push(Event::End(Tag::Paragraph));
+    }
+
+    fn render_title<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        if let Some(title) = params.title.as_ref().cloned() {
+            Self::raw("\\begin{center}\n\\LARGE ", events);
+            events.push(Event::Text(title.into()));
+            Self::raw("\n\\end{center}\n", events);
+        }
+    }
+
+    fn render_subtitle<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        if let Some(subtitle) = params.subtitle.as_ref().cloned() {
+            Self::raw("\\begin{center}\n\\large ", events);
+            events.push(Event::Text(subtitle.into()));
+            Self::raw("\n\\end{center}\n", events);
+        }
+    }
+
+    fn render_authors<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        if params.authors.is_empty() {
+            return;
+        }
+
+        Self::raw("\\begin{center}\n", events);
+        for (index, author) in params.authors.iter().cloned().enumerate() {
+            if index > 0 {
+                Self::raw(" \\\\\n", events);
+            }
+            events.push(Event::Text(author.into()));
+        }
+        Self::raw("\n\\end{center}\n", events);
+    }
+}
+
+fn heading_command(level: u32) -> &'static str {
+    match level {
+        1 => r"\section*{",
+        2 => r"\subsection*{",
+        3 => r"\subsubsection*{",
+        _ => r"\paragraph*{",
+    }
+}
+
+/// Writes events in LaTeX.
+pub fn push_latex<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    let mut in_code_block = false;
+
+    for event in iter {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph => {},
+                Tag::Heading(level) => *s += heading_command(level),
+                Tag::BlockQuote => *s += "\\begin{quote}\n",
+                Tag::CodeBlock(_) => {
+                    in_code_block = true;
+                    *s += "\\begin{verbatim}\n";
+                },
+                Tag::List(Some(_)) => *s += "\\begin{enumerate}\n",
+                Tag::List(None) => *s += "\\begin{itemize}\n",
+                Tag::Item => *s += "\\item ",
+                Tag::Emphasis => *s += r"\emph{",
+                Tag::Strong => *s += r"\textbf{",
+                Tag::Link(_, url, _) => {
+                    *s += r"\href{";
+                    *s += &escape_url(&url);
+                    *s += "}{";
+                },
+                _ => {},
+            },
+            Event::End(tag) => match tag {
+                Tag::Paragraph => *s += "\n\n",
+                Tag::Heading(_) => *s += "}\n\n",
+                Tag::BlockQuote => *s += "\\end{quote}\n",
+                Tag::CodeBlock(_) => {
+                    in_code_block = false;
+                    *s += "\\end{verbatim}\n";
+                },
+                Tag::List(Some(_)) => *s += "\\end{enumerate}\n",
+                Tag::List(None) => *s += "\\end{itemize}\n",
+                Tag::Item => *s += "\n",
+                Tag::Emphasis | Tag::Strong | Tag::Link(..) => *s += "}",
+                _ => {},
+            },
+            Event::Text(text) => {
+                if in_code_block {
+                    *s += &text;
+                } else {
+                    *s += &escape_latex(&text);
+                }
+            },
+            Event::Code(text) => {
+                *s += r"\texttt{";
+                *s += &escape_latex(&text);
+                *s += "}";
+            },
+            Event::Html(html) => {
+                if let Some(RawLatex(latex)) = RawLatex::from_html(&html) {
+                    *s += latex;
+                }
+            },
+            Event::FootnoteReference(_) | Event::TaskListMarker(_) => {},
+            Event::SoftBreak => s.push('\n'),
+            Event::HardBreak => *s += "\\\\\n",
+            Event::Rule => *s += "\\par\\noindent\\hrulefill\\par\n",
+        }
+    }
+}
+
+/// A writer of a standalone LaTeX document.
+#[derive(Debug,Clone)]
+pub struct LatexDocument {
+    params: Params,
+    document_class: String,
+    packages: Vec<String>,
+    make_title: bool,
+}
+
+impl LatexDocument {
+    pub fn new(params: Params) -> Self {
+        Self {
+            params,
+            document_class: "article".to_owned(),
+            packages: vec!["hyperref".to_owned()],
+            make_title: true,
+        }
+    }
+
+    pub fn document_class<S: Into<String>>(self, class: S) -> Self {
+        Self {
+            document_class: class.into(),
+            ..self
+        }
+    }
+
+    /// Adds a package loaded in the preamble.
+    pub fn package<S: Into<String>>(mut self, package: S) -> Self {
+        self.packages.push(package.into());
+        self
+    }
+
+    /// Sets whether `\maketitle` is put at the beginning of the document.
+    pub fn make_title(self, val: bool) -> Self {
+        Self {
+            make_title: val,
+            ..self
+        }
+    }
+
+    fn preamble(&self) -> String {
+        let mut s = format!("\\documentclass{{{}}}\n", self.document_class);
+
+        for package in self.packages.iter() {
+            s += &format!("\\usepackage{{{}}}\n", package);
+        }
+        s += "\n";
+        s += LATEX_MACROS;
+        s += "\n";
+
+        if let Some(title) = self.params.title.as_ref() {
+            s += &format!("\\title{{{}", escape_latex(title));
+            if let Some(subtitle) = self.params.subtitle.as_ref() {
+                s += &format!("\\\\\n\\large {}", escape_latex(subtitle));
+            }
+            s += "}\n";
+        }
+        let authors: Vec<String> = self.params.authors.iter()
+            .map(|author| escape_latex(author))
+            .collect();
+        s += &format!("\\author{{{}}}\n", authors.join(" \\and "));
+        s += "\\date{}\n";
+        s += "\n\\begin{document}\n";
+
+        if self.make_title && self.params.title.is_some() {
+            s += "\\maketitle\n";
+        }
+        s += "\n";
+
+        s
+    }
+
+    pub fn push_latex<'a, I>(&self, s: &mut String, iter: I)
+    where
+        I: Iterator<Item=Event<'a>>,
+    {
+        *s += &self.preamble();
+        push_latex(s, iter);
+        *s += "\\end{document}\n";
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use crate::MdPlayScript;
+
+    fn convert(s: &str) -> String {
+        let mut buf = String::new();
+        push_latex(&mut buf, MdPlayScript::with_renderer(Parser::new(s), LatexRenderer::new()));
+        buf
+    }
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape_latex(r"50% & $5 #1 a_b {x} ~ ^ \ <a|b>"),
+            r"50\% \& \$5 \#1 a\_b \{x\} \textasciitilde{} \textasciicircum{} \textbackslash{} \textless{}a\textbar{}b\textgreater{}");
+    }
+
+    #[test]
+    fn escape_urls_of_links() {
+        assert_eq!(convert("[x](https://example.com/~a/b_c?d=50%#e)"),
+            "\\href{https://example.com/~a/b_c?d=50\\%\\#e}{x}\n\n");
+    }
+
+    #[test]
+    fn drop_html_blocks() {
+        let s = r#"<!--
+playscript-cast
+A
+-->
+
+<div>
+raw
+</div>
+
+A> Hi"#;
+        assert_eq!(convert(s), r"\begin{speech}
+\character{A} Hi
+\end{speech}
+");
+    }
+
+    #[test]
+    fn render_speech_to_latex() {
+        assert_eq!(convert("A (running)> 100% *sure* (exit)"), r"\begin{speech}
+\character{A} \stagedirection{running} 100\% \emph{sure} \stagedirection{exit}
+\end{speech}
+");
+    }
+
+    #[test]
+    fn render_monologue_and_paragraph_to_latex() {
+        let s = r#"## Act_1
+
+<!-- playscript-monologue-begin -->
+Alone. (sighs)
+<!-- playscript-monologue-end -->
+
+Normal <b>line</b>."#;
+        assert_eq!(convert(s), r"\subsection*{Act\_1}
+
+\begin{monologue}
+Alone. \stagedirection{sighs}
+\end{monologue}
+
+Normal line.
+
+");
+    }
+
+    #[test]
+    fn standalone_document() {
+        let params = Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: Some("Acte I".to_owned()),
+            authors: vec!["Beaumarchais".to_owned(), "B & C".to_owned()],
+        };
+        let doc = LatexDocument::new(params);
+        let mut buf = String::new();
+        doc.push_latex(&mut buf, MdPlayScript::with_renderer(Parser::new("A> Hi"), LatexRenderer::new()));
+        assert!(buf.starts_with("\\documentclass{article}\n\\usepackage{hyperref}\n"));
+        assert!(buf.contains(LATEX_MACROS));
+        assert!(buf.contains("\\title{Le Mariage de Figaro\\\\\n\\large Acte I}\n"));
+        assert!(buf.contains("\\author{Beaumarchais \\and B \\& C}\n"));
+        assert!(buf.contains("\\begin{document}\n\\maketitle\n"));
+        assert!(buf.ends_with("\\end{speech}\n\\end{document}\n"));
+    }
+}
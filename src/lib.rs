@@ -2,7 +2,22 @@
 pub mod parser;
 pub mod speech;
 pub mod renderer;
+pub mod anchor;
+pub mod ruby;
+pub mod vertical;
 pub mod interface;
+pub mod cast;
+pub mod structure;
+pub mod document;
+pub mod diagnostics;
+pub mod model;
+pub mod stats;
+pub mod sides;
+pub mod latex;
+pub mod fountain;
+pub mod fdx;
+pub mod tei;
+pub mod preprocessor;
 
 pub use interface::{MdPlayScript, Options, Params};
 
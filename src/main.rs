@@ -0,0 +1,733 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use eyre::{eyre, Result, WrapErr};
+use structopt::StructOpt;
+use pulldown_cmark::{Event, Parser, Tag};
+use pulldown_cmark::escape::escape_html;
+use mdplayscript::anchor::IdScheme;
+use mdplayscript::diagnostics::{Lint, Severity};
+use mdplayscript::document::{HtmlDocument, Style, StyleSheet};
+use mdplayscript::interface::{MdPlayScriptBuilder, Options, Params, markdown_options};
+use mdplayscript::fdx::push_fdx;
+use mdplayscript::fountain::{parse_fountain, push_fountain};
+use mdplayscript::latex::{LatexDocument, LatexRenderer};
+use mdplayscript::renderer::{Concealment, LineLearning};
+use mdplayscript::model::Play;
+use mdplayscript::stats::{Stats, SpeakingRate, format_minutes, is_valid_rate};
+use mdplayscript::sides::Sides;
+use mdplayscript::tei::TeiDocument;
+use mdplayscript::parser::{Delimiters, split_speech_heading_with};
+use mdplayscript::speech::parse_heading_with;
+
+#[derive(Debug,StructOpt)]
+#[structopt(about = "Converts and inspects play scripts written in mdplayscript")]
+enum Command {
+    /// Converts a play script into an HTML document
+    Build(BuildOpt),
+    /// Reports mistakes of a play script and exits with an error if any
+    Check(CheckOpt),
+    /// Counts speeches, lines and words of each character
+    Stats(StatsOpt),
+    /// Normalizes speech headings of a play script
+    Fmt(FmtOpt),
+    /// Exports a play script into another format
+    Export(ExportOpt),
+    /// Imports a script in another format into a play script
+    Import(ImportOpt),
+    /// Makes rehearsal sides with the speeches of characters and their cues
+    Sides(SidesOpt),
+}
+
+#[derive(Debug,StructOpt)]
+struct InputOpt {
+    /// Input file; reads from stdin if omitted or `-`
+    #[structopt(parse(from_os_str))]
+    input: Option<PathBuf>,
+}
+
+#[derive(Debug,StructOpt)]
+struct OutputOpt {
+    /// Output file; writes to stdout if omitted or `-`
+    #[structopt(long,short,parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug,StructOpt)]
+struct PlayOpt {
+    #[structopt(long,short)]
+    title: Option<String>,
+    #[structopt(long)]
+    subtitle: Option<String>,
+    /// Author of the play; repeat for several authors
+    #[structopt(long,number_of_values=1)]
+    authors: Vec<String>,
+    #[structopt(flatten)]
+    lang: LanguageOpt,
+}
+
+#[derive(Debug,StructOpt)]
+struct LanguageOpt {
+    /// Language of the play script; `ja` selects Japanese options and style sheet
+    #[structopt(long,short,default_value="")]
+    language: String,
+    /// Accepts full-width `＞` and `（）` as delimiters; enabled by `--language ja`
+    #[structopt(long)]
+    full_width: bool,
+    /// Accepts tortoise shell brackets `〔〕` as delimiters of directions
+    #[structopt(long)]
+    tortoise_shell: bool,
+    /// Keeps full-width brackets of directions in output
+    #[structopt(long)]
+    preserve_brackets: bool,
+}
+
+impl PlayOpt {
+    fn params(&self) -> Params {
+        Params {
+            title: self.title.clone(),
+            subtitle: self.subtitle.clone(),
+            authors: self.authors.clone(),
+        }
+    }
+
+    fn options(&self) -> Options {
+        self.lang.options()
+    }
+}
+
+impl LanguageOpt {
+    fn options(&self) -> Options {
+        let options = if self.language == "ja" {
+            Options::default_ja()
+        } else {
+            Options::default()
+        };
+
+        options.delimiters(self.delimiters())
+    }
+
+    fn delimiters(&self) -> Delimiters {
+        Delimiters {
+            full_width: self.full_width || self.language == "ja",
+            tortoise_shell: self.tortoise_shell,
+            preserve_brackets: self.preserve_brackets,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug,StructOpt)]
+struct CheckOpt {
+    /// Output format: text, json
+    #[structopt(long,short,default_value="text")]
+    format: ReportFormat,
+    /// Known character; speeches of other characters are reported as errors
+    #[structopt(long="character",short="c",number_of_values=1)]
+    characters: Vec<String>,
+    #[structopt(flatten)]
+    lang: LanguageOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug,StructOpt)]
+struct RateOpt {
+    /// Speaking rate in words per minute for estimating running time
+    #[structopt(long,default_value="150",parse(try_from_str=parse_rate))]
+    wpm: f64,
+    /// Speaking rate in characters per minute for Japanese and other CJK scripts
+    #[structopt(long,default_value="300",parse(try_from_str=parse_rate))]
+    cpm: f64,
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|_| format!("invalid rate: {}", s))?;
+    if is_valid_rate(rate) {
+        Ok(rate)
+    } else {
+        Err(format!("rate must be positive and finite: {}", s))
+    }
+}
+
+impl RateOpt {
+    fn rate(&self) -> SpeakingRate {
+        SpeakingRate::new()
+            .words_per_minute(self.wpm)
+            .cjk_characters_per_minute(self.cpm)
+    }
+}
+
+#[derive(Debug,StructOpt)]
+struct StatsOpt {
+    /// Output format: text, json
+    #[structopt(long,short,default_value="text")]
+    format: ReportFormat,
+    #[structopt(flatten)]
+    rate: RateOpt,
+    #[structopt(flatten)]
+    lang: LanguageOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug,StructOpt)]
+struct BuildOpt {
+    #[structopt(flatten)]
+    play: PlayOpt,
+    /// Embeds the style sheet into the document instead of linking to it
+    #[structopt(long)]
+    embed_css: bool,
+    /// Shows the estimated running time under each scene heading
+    #[structopt(long)]
+    running_time: bool,
+    /// Scheme of anchor IDs of speeches: counter, scene, character, hash
+    #[structopt(long,default_value="counter")]
+    speech_ids: IdScheme,
+    /// Character whose lines are hidden for learning; repeat for several characters
+    #[structopt(long="learn",number_of_values=1)]
+    learn: Vec<String>,
+    /// How the lines are hidden: collapse, first-letters
+    #[structopt(long,default_value="collapse")]
+    learn_style: Concealment,
+    /// Typesets the play vertically with the style sheet `play_ja_vertical.css`
+    #[structopt(long)]
+    vertical: bool,
+    #[structopt(flatten)]
+    rate: RateOpt,
+    #[structopt(flatten)]
+    output: OutputOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug)]
+enum ExportFormat {
+    Json,
+    Latex,
+    Fountain,
+    Fdx,
+    Tei,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "latex" => Ok(ExportFormat::Latex),
+            "fountain" => Ok(ExportFormat::Fountain),
+            "fdx" => Ok(ExportFormat::Fdx),
+            "tei" => Ok(ExportFormat::Tei),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug,StructOpt)]
+struct ExportOpt {
+    /// Output format: json, latex, fountain, fdx, tei
+    #[structopt(long,short,default_value="json")]
+    format: ExportFormat,
+    /// Writes each line of speeches as a verse line (tei only)
+    #[structopt(long)]
+    verse: bool,
+    #[structopt(flatten)]
+    play: PlayOpt,
+    #[structopt(flatten)]
+    output: OutputOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug)]
+enum ImportFormat {
+    Fountain,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fountain" => Ok(ImportFormat::Fountain),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug,StructOpt)]
+struct ImportOpt {
+    /// Input format: fountain
+    #[structopt(long,short,default_value="fountain")]
+    format: ImportFormat,
+    #[structopt(flatten)]
+    output: OutputOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug)]
+enum SidesFormat {
+    Html,
+    Markdown,
+}
+
+impl FromStr for SidesFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(SidesFormat::Html),
+            "markdown" => Ok(SidesFormat::Markdown),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug,StructOpt)]
+struct SidesOpt {
+    /// Character played by the actor; repeat for doubled roles
+    #[structopt(long="character",short="c",number_of_values=1,required=true)]
+    characters: Vec<String>,
+    /// Number of words of each cue
+    #[structopt(long,default_value="5")]
+    cue_words: usize,
+    /// Output format: html, markdown
+    #[structopt(long,short,default_value="html")]
+    format: SidesFormat,
+    /// Embeds the style sheet into the document instead of linking to it (html only)
+    #[structopt(long)]
+    embed_css: bool,
+    #[structopt(flatten)]
+    play: PlayOpt,
+    #[structopt(flatten)]
+    output: OutputOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+#[derive(Debug,StructOpt)]
+struct FmtOpt {
+    #[structopt(flatten)]
+    lang: LanguageOpt,
+    #[structopt(flatten)]
+    output: OutputOpt,
+    #[structopt(flatten)]
+    input: InputOpt,
+}
+
+fn is_stdio(path: &Option<PathBuf>) -> bool {
+    match path {
+        Some(path) => path.as_os_str() == "-",
+        None => true,
+    }
+}
+
+fn read_input(opt: &InputOpt) -> Result<String> {
+    let mut text = String::new();
+
+    if is_stdio(&opt.input) {
+        io::stdin().read_to_string(&mut text)
+            .wrap_err("failed to read from stdin")?;
+    } else {
+        let path = opt.input.as_ref().unwrap();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    }
+
+    Ok(text)
+}
+
+fn write_output(opt: &OutputOpt, content: &str) -> Result<()> {
+    if is_stdio(&opt.output) {
+        io::stdout().write_all(content.as_bytes())
+            .wrap_err("failed to write to stdout")?;
+    } else {
+        let path = opt.output.as_ref().unwrap();
+        File::create(path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, s).unwrap();
+    escaped
+}
+
+fn make_title(params: &Params) -> String {
+    let mut s = format!(r#"<div class="cover"><h1 class="title">{title}</h1>"#,
+        title=escape(params.title.as_deref().unwrap_or("")));
+
+    if let Some(subtitle) = params.subtitle.as_ref() {
+        s += &format!("<p class=\"subtitle\">{}</p>", escape(subtitle));
+    }
+
+    s += "<div class=\"authors\">";
+
+    for author in params.authors.iter() {
+        s += &format!("<p>{}</p>", escape(author));
+    }
+
+    s += "</div></div>";
+
+    s
+}
+
+fn build(opt: BuildOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+
+    let mut options = opt.play.options().id_scheme(opt.speech_ids);
+    if opt.running_time {
+        options = options.running_time(Some(opt.rate.rate()));
+    }
+    if !opt.learn.is_empty() {
+        options = options.line_learning(Some(LineLearning::new(opt.learn.clone(), opt.learn_style)));
+    }
+    if opt.vertical {
+        options = options.vertical(true);
+    }
+
+    let parser = MdPlayScriptBuilder::new()
+        .options(options)
+        .params(opt.play.params())
+        .make_title(Box::new(make_title))
+        .build(Parser::new_ext(&text, markdown_options()));
+
+    let style = if opt.vertical {
+        Style::JapaneseVertical
+    } else {
+        Style::for_lang(&opt.play.lang.language)
+    };
+    let stylesheet = if opt.embed_css {
+        StyleSheet::Inline(style)
+    } else {
+        StyleSheet::Link(format!("./{}", style.file_name()))
+    };
+    let mut document = HtmlDocument::new(opt.play.title.as_deref().unwrap_or(""))
+        .stylesheet(stylesheet);
+    if !opt.play.lang.language.is_empty() {
+        document = document.lang(opt.play.lang.language.as_str());
+    }
+
+    let mut output = String::new();
+    document.push_html(&mut output, parser);
+
+    write_output(&opt.output, &output)
+}
+
+/// Collects character names of all speeches in order of appearance.
+///
+/// Aliases declared in the cast are resolved into the canonical names.
+fn collect_characters(text: &str, options: &Options) -> Vec<String> {
+    Play::from_events(Parser::new_ext(text, markdown_options()), options, Params::default())
+        .speeches()
+        .map(|speech| speech.character.clone())
+        .collect()
+}
+
+fn check(opt: CheckOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+    let options = opt.lang.options();
+    let characters = collect_characters(&text, &options);
+    let mut distinct = characters.clone();
+    distinct.sort();
+    distinct.dedup();
+
+    let mut lint = Lint::new(options);
+    if !opt.characters.is_empty() {
+        lint = lint.characters(opt.characters.clone());
+    }
+    let diagnostics = lint.run(&text);
+    let errors = diagnostics.count(Severity::Error);
+    let warnings = diagnostics.count(Severity::Warning);
+    let file = match opt.input.input.as_ref() {
+        Some(path) if !is_stdio(&opt.input.input) => path.display().to_string(),
+        _ => "<stdin>".to_owned(),
+    };
+
+    match opt.format {
+        ReportFormat::Text => {
+            for diagnostic in diagnostics.into_vec().iter() {
+                println!("{}:{}", file, diagnostic);
+            }
+            println!("{} speeches by {} characters; {} errors, {} warnings",
+                characters.len(), distinct.len(), errors, warnings);
+        },
+        ReportFormat::Json => {
+            let report = serde_json::json!({
+                "file": file,
+                "speeches": characters.len(),
+                "characters": distinct.len(),
+                "errors": errors,
+                "warnings": warnings,
+                "diagnostics": diagnostics.into_vec(),
+            });
+            let s = serde_json::to_string_pretty(&report)
+                .wrap_err("failed to serialize the diagnostics")?;
+            println!("{}", s);
+        },
+    }
+
+    if errors > 0 {
+        return Err(eyre!("{} errors found", errors));
+    }
+    if characters.is_empty() {
+        return Err(eyre!("no speeches found"));
+    }
+
+    Ok(())
+}
+
+fn stats(opt: StatsOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+    let play = Play::from_events(Parser::new_ext(&text, markdown_options()), &opt.lang.options(), Params::default());
+    let stats = Stats::from_play_with_rate(&play, &opt.rate.rate());
+
+    match opt.format {
+        ReportFormat::Text => {
+            println!("character\tspeeches\tlines\twords\tcjk\tshare\tlongest");
+            for character in stats.characters.iter() {
+                let longest = character.longest_speech.as_ref()
+                    .map(|longest| format!("#{} {}", longest.index + 1, longest.opening))
+                    .unwrap_or_default();
+                println!("{}\t{}\t{}\t{}\t{}\t{:.1}%\t{}",
+                    character.character, character.speeches, character.lines, character.words,
+                    character.cjk_characters, character.share * 100.0, longest);
+            }
+            println!("total\t{}\t{}\t{}\t{}\t100.0%\t",
+                stats.speeches, stats.lines, stats.words, stats.cjk_characters);
+
+            println!();
+            println!("scene\trunning time");
+            for scene in stats.running_time.scenes.iter() {
+                let title: Vec<&str> = scene.act.iter().chain(scene.scene.iter())
+                    .map(|title| title.as_str())
+                    .collect();
+                let title = if title.is_empty() { "-".to_owned() } else { title.join(" / ") };
+                println!("{}\t{}", title, format_minutes(scene.minutes));
+            }
+            println!("total\t{}", format_minutes(stats.running_time.minutes));
+        },
+        ReportFormat::Json => {
+            let s = serde_json::to_string_pretty(&stats)
+                .wrap_err("failed to serialize the statistics")?;
+            println!("{}", s);
+        },
+    }
+
+    Ok(())
+}
+
+/// Rewrites a speech heading into the canonical form `Character (direction)> body`.
+///
+/// Full-width delimiters are kept as written, without spaces around them.
+fn format_speech_line(s: &str, delimiters: &Delimiters) -> Option<String> {
+    let (heading, body) = split_speech_heading_with(s, delimiters)?;
+    let angle = s[heading.len()..].chars().next()?;
+    let open = heading.chars().find(|c| delimiters.is_open(*c)).unwrap_or('(');
+    let close = heading.chars().find(|c| delimiters.is_close(*c)).unwrap_or(match open {
+        '（' => '）',
+        '〔' => '〕',
+        _ => ')',
+    });
+    let heading = parse_heading_with(heading, &Delimiters {
+        preserve_brackets: false,
+        ..*delimiters
+    });
+    let mut line = heading.character.to_string();
+
+    let direction: String = heading.direction.0.iter()
+        .filter_map(|e| match e {
+            Event::Text(s) => Some(s.trim()),
+            _ => None,
+        })
+        .collect();
+    if !direction.is_empty() {
+        if open.is_ascii() {
+            line.push(' ');
+        }
+        line += &format!("{}{}{}", open, direction, close);
+    }
+
+    line.push(angle);
+    let body = body.trim_start();
+    if !body.is_empty() {
+        if angle.is_ascii() {
+            line.push(' ');
+        }
+        line += body;
+    }
+
+    Some(line)
+}
+
+fn fmt(opt: FmtOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+    let delimiters = opt.lang.delimiters();
+    let mut in_paragraph = false;
+    let mut at_line_head = false;
+
+    let events = Parser::new_ext(&text, markdown_options()).map(|event| {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                in_paragraph = true;
+                at_line_head = true;
+                event
+            },
+            Event::End(Tag::Paragraph) => {
+                in_paragraph = false;
+                event
+            },
+            Event::SoftBreak | Event::HardBreak => {
+                at_line_head = true;
+                event
+            },
+            Event::Text(s) if in_paragraph && at_line_head => {
+                at_line_head = false;
+                match format_speech_line(&s, &delimiters) {
+                    Some(line) => Event::Text(line.into()),
+                    None => Event::Text(s),
+                }
+            },
+            event => {
+                at_line_head = false;
+                event
+            },
+        }
+    });
+
+    let mut output = String::new();
+    pulldown_cmark_to_cmark::cmark(events, &mut output, None)
+        .map_err(|_| eyre!("failed to format the play script"))?;
+    output.push('\n');
+
+    write_output(&opt.output, &output)
+}
+
+fn export(opt: ExportOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+    let parser = Parser::new_ext(&text, markdown_options());
+
+    let output = match opt.format {
+        ExportFormat::Json => {
+            let play = Play::from_events(parser, &opt.play.options(), opt.play.params());
+            let mut s = serde_json::to_string_pretty(&play)
+                .wrap_err("failed to serialize the play")?;
+            s.push('\n');
+            s
+        },
+        ExportFormat::Latex => {
+            let mdplay = MdPlayScriptBuilder::new()
+                .options(opt.play.options())
+                .params(opt.play.params())
+                .build_with_renderer(parser, LatexRenderer::new());
+            let mut s = String::new();
+            LatexDocument::new(opt.play.params()).push_latex(&mut s, mdplay);
+            s
+        },
+        ExportFormat::Fountain => {
+            let play = Play::from_events(parser, &opt.play.options(), opt.play.params());
+            let mut s = String::new();
+            push_fountain(&mut s, &play);
+            s
+        },
+        ExportFormat::Fdx => {
+            let play = Play::from_events(parser, &opt.play.options(), opt.play.params());
+            let mut s = String::new();
+            push_fdx(&mut s, &play);
+            s
+        },
+        ExportFormat::Tei => {
+            let play = Play::from_events(parser, &opt.play.options(), opt.play.params());
+            let mut s = String::new();
+            TeiDocument::new().verse(opt.verse).push_tei(&mut s, &play);
+            s
+        },
+    };
+
+    write_output(&opt.output, &output)
+}
+
+fn import(opt: ImportOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+
+    let output = match opt.format {
+        ImportFormat::Fountain => parse_fountain(&text).to_markdown(),
+    };
+
+    write_output(&opt.output, &output)
+}
+
+fn sides(opt: SidesOpt) -> Result<()> {
+    let text = read_input(&opt.input)?;
+    let options = opt.play.options();
+    let play = Play::from_events(Parser::new_ext(&text, markdown_options()), &options, opt.play.params());
+    let sides = Sides::new(opt.characters.clone()).cue_words(opt.cue_words);
+
+    let output = match opt.format {
+        SidesFormat::Markdown => sides.to_markdown(&play),
+        SidesFormat::Html => {
+            let style = Style::for_lang(&opt.play.lang.language);
+            let stylesheet = if opt.embed_css {
+                StyleSheet::Inline(style)
+            } else {
+                StyleSheet::Link(format!("./{}", style.file_name()))
+            };
+            let title = format!("{} ({})",
+                opt.play.title.as_deref().unwrap_or(""),
+                opt.characters.join(", "));
+            let mut document = HtmlDocument::new(title.trim_start())
+                .stylesheet(stylesheet);
+            if !opt.play.lang.language.is_empty() {
+                document = document.lang(opt.play.lang.language.as_str());
+            }
+
+            let markdown = sides.to_markdown(&play);
+            let parser = MdPlayScriptBuilder::new()
+                .options(options)
+                .build(Parser::new_ext(&markdown, markdown_options()));
+            let mut s = String::new();
+            document.push_html(&mut s, parser);
+            s
+        },
+    };
+
+    write_output(&opt.output, &output)
+}
+
+fn main() -> Result<()> {
+    match Command::from_args() {
+        Command::Build(opt) => build(opt),
+        Command::Check(opt) => check(opt),
+        Command::Stats(opt) => stats(opt),
+        Command::Fmt(opt) => fmt(opt),
+        Command::Export(opt) => export(opt),
+        Command::Import(opt) => import(opt),
+        Command::Sides(opt) => sides(opt),
+    }
+}
@@ -0,0 +1,611 @@
+//! A document model of play scripts.
+//!
+//! A play consists of acts, an act consists of scenes and a scene consists of blocks.
+//! Headings begin acts and scenes as `Options::structure` specifies; by default, level 2
+//! headings begin acts and level 3 headings begin scenes.
+//! Blocks before the first act or scene are put into an act or a scene without a title.
+//!
+//! The model is built from Markdown events by `Play::from_events` and can be serialized
+//! with serde. Characters written with aliases declared by `playscript-cast` directives are
+//! resolved into the canonical names, and ruby annotations of characters are removed.
+use std::mem;
+use pulldown_cmark::{Event, Tag};
+use serde::{Serialize, Deserialize};
+use crate::cast::Cast;
+use crate::interface::{Mode, Options, Params, Directive, CommentJoiner, parse_directive};
+use crate::parser::{Delimiters, FuseOnParagraphEnd, Speeches};
+use crate::speech;
+use crate::structure::{Structure, Unit};
+use crate::ruby::strip_ruby;
+
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+pub struct Play {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    /// Characters in order of first appearance.
+    pub characters: Vec<String>,
+    pub acts: Vec<Act>,
+}
+
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+pub struct Act {
+    pub title: Option<String>,
+    pub scenes: Vec<Scene>,
+}
+
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+pub struct Scene {
+    pub title: Option<String>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Speech(Speech),
+    /// A paragraph which only consists of directions.
+    Direction { content: Vec<Inline> },
+    Monologue { content: Vec<Inline> },
+    /// A paragraph which is not a speech.
+    Prose { content: Vec<Inline> },
+    /// A heading which is neither an act nor a scene.
+    Heading { level: u32, content: Vec<Inline> },
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Speech {
+    pub character: String,
+    pub direction: Vec<Inline>,
+    pub body: Vec<Inline>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Html(String),
+    SoftBreak,
+    HardBreak,
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Link { url: String, title: String, content: Vec<Inline> },
+    Image { url: String, title: String, content: Vec<Inline> },
+    Direction(Vec<Inline>),
+}
+
+impl Inline {
+    /// Returns the plain text of the inline element.
+    pub fn plain_text(&self) -> String {
+        match self {
+            Inline::Text(s) | Inline::Code(s) => s.clone(),
+            Inline::Html(_) => String::new(),
+            Inline::SoftBreak | Inline::HardBreak => " ".to_owned(),
+            Inline::Emphasis(v) | Inline::Strong(v) | Inline::Strikethrough(v)
+                | Inline::Direction(v) => plain_text(v),
+            Inline::Link { content, .. } | Inline::Image { content, .. } => plain_text(content),
+        }
+    }
+}
+
+/// Concatenates the plain texts of inline elements.
+pub fn plain_text(inlines: &[Inline]) -> String {
+    inlines.iter()
+        .map(|inline| inline.plain_text())
+        .collect()
+}
+
+/// Converts a sequence of events into nested inline elements.
+#[derive(Debug,Default)]
+struct InlineBuilder<'a> {
+    stack: Vec<(Tag<'a>, Vec<Inline>)>,
+    current: Vec<Inline>,
+}
+
+impl<'a> InlineBuilder<'a> {
+    fn push_text(&mut self, s: &str) {
+        if let Some(Inline::Text(last)) = self.current.last_mut() {
+            last.push_str(s);
+        } else {
+            self.current.push(Inline::Text(s.to_owned()));
+        }
+    }
+
+    fn push_inline(&mut self, inline: Inline) {
+        self.current.push(inline);
+    }
+
+    fn push_event(&mut self, event: Event<'a>) {
+        match event {
+            Event::Start(tag) => {
+                let parent = mem::take(&mut self.current);
+                self.stack.push((tag, parent));
+            },
+            Event::End(_) => {
+                self.close();
+            },
+            Event::Text(s) => self.push_text(&s),
+            Event::Code(s) => self.push_inline(Inline::Code(s.to_string())),
+            Event::Html(s) => self.push_inline(Inline::Html(s.to_string())),
+            Event::FootnoteReference(s) => self.push_text(&s),
+            Event::SoftBreak => self.push_inline(Inline::SoftBreak),
+            Event::HardBreak => self.push_inline(Inline::HardBreak),
+            Event::Rule | Event::TaskListMarker(_) => {},
+        }
+    }
+
+    fn close(&mut self) {
+        let (tag, parent) = match self.stack.pop() {
+            Some(item) => item,
+            None => return,
+        };
+        let children = mem::replace(&mut self.current, parent);
+
+        let inline = match tag {
+            Tag::Emphasis => Inline::Emphasis(children),
+            Tag::Strong => Inline::Strong(children),
+            Tag::Strikethrough => Inline::Strikethrough(children),
+            Tag::Link(_, url, title) => Inline::Link {
+                url: url.to_string(),
+                title: title.to_string(),
+                content: children,
+            },
+            Tag::Image(_, url, title) => Inline::Image {
+                url: url.to_string(),
+                title: title.to_string(),
+                content: children,
+            },
+            _ => {
+                self.current.extend(children);
+                return;
+            },
+        };
+
+        self.current.push(inline);
+    }
+
+    fn finish(mut self) -> Vec<Inline> {
+        while !self.stack.is_empty() {
+            self.close();
+        }
+
+        self.current
+    }
+}
+
+fn convert_events<'a, I>(events: I) -> Vec<Inline>
+where
+    I: IntoIterator<Item=Event<'a>>,
+{
+    let mut builder = InlineBuilder::default();
+
+    for event in events.into_iter() {
+        builder.push_event(event);
+    }
+
+    builder.finish()
+}
+
+fn convert_direction<'a>(direction: speech::Direction<'a>) -> Vec<Inline> {
+    let mut inlines = convert_events(direction.0);
+    trim_inlines(&mut inlines);
+    inlines
+}
+
+fn convert_body<'a>(body: Vec<speech::Inline<'a>>) -> Vec<Inline> {
+    let mut builder = InlineBuilder::default();
+
+    for inline in body.into_iter() {
+        match inline {
+            speech::Inline::Event(event) => builder.push_event(event),
+            speech::Inline::Direction(direction) => {
+                builder.push_inline(Inline::Direction(convert_direction(direction)));
+            },
+        }
+    }
+
+    let mut inlines = builder.finish();
+    trim_inlines(&mut inlines);
+    inlines
+}
+
+/// Removes white spaces at the beginning and the end of inline elements.
+fn trim_inlines(inlines: &mut Vec<Inline>) {
+    if let Some(Inline::Text(s)) = inlines.first_mut() {
+        *s = s.trim_start().to_owned();
+        if s.is_empty() {
+            inlines.remove(0);
+        }
+    }
+
+    if let Some(Inline::Text(s)) = inlines.last_mut() {
+        *s = s.trim_end().to_owned();
+        if s.is_empty() {
+            inlines.pop();
+        }
+    }
+}
+
+impl<'a> From<speech::Speech<'a>> for Speech {
+    fn from(speech: speech::Speech<'a>) -> Speech {
+        Speech {
+            character: strip_ruby(&speech.heading.character),
+            direction: convert_direction(speech.heading.direction),
+            body: convert_body(speech.body),
+        }
+    }
+}
+
+fn is_direction_only(inlines: &[Inline]) -> bool {
+    let mut has_direction = false;
+
+    for inline in inlines.iter() {
+        match inline {
+            Inline::Direction(_) => has_direction = true,
+            Inline::Text(s) if s.trim().is_empty() => {},
+            Inline::SoftBreak | Inline::HardBreak => {},
+            _ => return false,
+        }
+    }
+
+    has_direction
+}
+
+impl Play {
+    pub fn new(params: Params) -> Self {
+        Self {
+            title: params.title,
+            subtitle: params.subtitle,
+            authors: params.authors,
+            ..Default::default()
+        }
+    }
+
+    /// Builds a play from Markdown events.
+    ///
+    /// Directives are interpreted in the same way as `MdPlayScript`.
+    /// Block elements other than paragraphs and headings are ignored.
+    pub fn from_events<'a, I>(iter: I, options: &Options, params: Params) -> Self
+    where
+        I: Iterator<Item=Event<'a>> + 'a,
+    {
+        let mode = if options.disabled_in_default {
+            Mode::Nop
+        } else {
+            Mode::PlayScript
+        };
+
+        Self::from_events_in_mode(iter, params, &options.structure, &options.delimiters, mode, Cast::new())
+    }
+
+    /// Builds a play from events in the middle of a document, which are read in the given mode
+    /// with the cast declared before them.
+    pub(crate) fn from_events_in_mode<'a, I>(iter: I, params: Params, structure: &Structure, delimiters: &Delimiters, mode: Mode, cast: Cast) -> Self
+    where
+        I: Iterator<Item=Event<'a>> + 'a,
+    {
+        let mut builder = PlayBuilder {
+            play: Play::new(params),
+            structure: structure.clone(),
+            // Brackets are a matter of rendering; directions of the model keep only the text.
+            delimiters: Delimiters {
+                preserve_brackets: false,
+                ..*delimiters
+            },
+            mode,
+            comments: CommentJoiner::default(),
+            cast,
+        };
+
+        builder.consume(iter);
+
+        builder.play
+    }
+
+    fn add_character(&mut self, character: &str) {
+        if !self.characters.iter().any(|c| c == character) {
+            self.characters.push(character.to_owned());
+        }
+    }
+
+    fn last_act(&mut self) -> &mut Act {
+        if self.acts.is_empty() {
+            self.acts.push(Act::default());
+        }
+
+        self.acts.last_mut().unwrap()
+    }
+
+    fn last_scene(&mut self) -> &mut Scene {
+        let act = self.last_act();
+
+        if act.scenes.is_empty() {
+            act.scenes.push(Scene::default());
+        }
+
+        act.scenes.last_mut().unwrap()
+    }
+
+    fn push_block(&mut self, block: Block) {
+        if let Block::Speech(speech) = &block {
+            let character = speech.character.clone();
+            self.add_character(&character);
+        }
+
+        self.last_scene().blocks.push(block);
+    }
+
+    /// Iterates over all speeches of the play.
+    pub fn speeches(&self) -> impl Iterator<Item=&Speech> {
+        self.acts.iter()
+            .flat_map(|act| act.scenes.iter())
+            .flat_map(|scene| scene.blocks.iter())
+            .filter_map(|block| match block {
+                Block::Speech(speech) => Some(speech),
+                _ => None,
+            })
+    }
+}
+
+struct PlayBuilder {
+    play: Play,
+    structure: Structure,
+    delimiters: Delimiters,
+    mode: Mode,
+    comments: CommentJoiner,
+    cast: Cast,
+}
+
+impl PlayBuilder {
+    fn consume<'a, I>(&mut self, mut iter: I)
+    where
+        I: Iterator<Item=Event<'a>> + 'a,
+    {
+        while let Some(event) = iter.next() {
+            match event {
+                Event::Html(s) => {
+                    if let Some(html) = self.comments.push(&s) {
+                        self.dispatch_directive(&html);
+                    }
+                },
+                Event::Start(Tag::Paragraph) if !self.mode.is_off() => {
+                    let mut speeches = Speeches::with_delimiters(FuseOnParagraphEnd::new(iter), self.delimiters);
+
+                    for speech in speeches.by_ref() {
+                        self.dispatch_speech(speech);
+                    }
+
+                    iter = speeches.into_inner().into_inner();
+                },
+                Event::Start(Tag::Paragraph) => {
+                    let events = iter.by_ref()
+                        .take_while(|e| *e != Event::End(Tag::Paragraph));
+                    let mut content = convert_events(events);
+                    trim_inlines(&mut content);
+                    self.play.push_block(Block::Prose { content });
+                },
+                Event::Start(Tag::Heading(level)) => {
+                    let events = iter.by_ref()
+                        .take_while(|e| *e != Event::End(Tag::Heading(level)));
+                    let content = convert_events(events);
+                    self.dispatch_heading(level, content);
+                },
+                Event::Start(_) => {
+                    skip_block(&mut iter);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn dispatch_directive(&mut self, s: &str) {
+        match parse_directive(s) {
+            Some(Directive::MonologueBegin) => {
+                self.mode = Mode::Monologue;
+            },
+            Some(Directive::MonologueEnd) | Some(Directive::PlayScriptOn) => {
+                self.mode = Mode::PlayScript;
+            },
+            Some(Directive::PlayScriptOff) => {
+                self.mode = Mode::Nop;
+            },
+            Some(Directive::Cast(cast)) => {
+                self.cast.extend(cast);
+            },
+            _ => {},
+        }
+    }
+
+    fn dispatch_speech<'a>(&mut self, events: Vec<Event<'a>>) {
+        let block = match speech::parse_speech_with(events, &self.delimiters) {
+            Ok(speech) => {
+                let mut speech = Speech::from(speech);
+                if let Some(name) = self.cast.resolve(&speech.character) {
+                    speech.character = name.to_owned();
+                }
+                Block::Speech(speech)
+            },
+            Err(para) if self.mode.is_monologue() => Block::Monologue {
+                content: convert_body(speech::parse_body_with(para, &self.delimiters)),
+            },
+            Err(para) => {
+                let content = convert_body(speech::parse_body_with(para, &self.delimiters));
+                if is_direction_only(&content) {
+                    Block::Direction { content }
+                } else {
+                    Block::Prose { content }
+                }
+            },
+        };
+
+        self.play.push_block(block);
+    }
+
+    fn dispatch_heading(&mut self, level: u32, content: Vec<Inline>) {
+        let title = plain_text(&content);
+
+        match self.structure.unit(level, &title) {
+            Some(Unit::Act) => {
+                self.play.acts.push(Act {
+                    title: Some(title),
+                    scenes: Vec::new(),
+                });
+            },
+            Some(Unit::Scene) => {
+                self.play.last_act().scenes.push(Scene {
+                    title: Some(title),
+                    blocks: Vec::new(),
+                });
+            },
+            None => {
+                self.play.push_block(Block::Heading { level, content });
+            },
+        }
+    }
+}
+
+/// Skips events until the end of the block which has already started.
+fn skip_block<'a, I>(iter: &mut I)
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    let mut depth = 1usize;
+
+    for event in iter {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {},
+        }
+
+        if depth == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn parse(s: &str) -> Play {
+        Play::from_events(Parser::new(s), &Options::default(), Params::default())
+    }
+
+    fn text(s: &str) -> Inline {
+        Inline::Text(s.to_owned())
+    }
+
+    #[test]
+    fn build_play_with_acts_and_scenes() {
+        let s = r#"Prologue.
+
+## Act I
+
+### Scene 1
+
+A (running)> Hello! (exit)
+B> *Good* bye.
+
+(B sits.)
+
+#### Note
+"#;
+        let play = parse(s);
+        assert_eq!(play.characters, vec!["A".to_owned(), "B".to_owned()]);
+        assert_eq!(play.acts.len(), 2);
+        assert_eq!(play.acts[0], Act {
+            title: None,
+            scenes: vec![Scene {
+                title: None,
+                blocks: vec![Block::Prose { content: vec![text("Prologue.")] }],
+            }],
+        });
+        assert_eq!(play.acts[1].title.as_deref(), Some("Act I"));
+        assert_eq!(play.acts[1].scenes[0].title.as_deref(), Some("Scene 1"));
+        assert_eq!(play.acts[1].scenes[0].blocks, vec![
+            Block::Speech(Speech {
+                character: "A".to_owned(),
+                direction: vec![text("running")],
+                body: vec![text("Hello! "), Inline::Direction(vec![text("exit")])],
+            }),
+            Block::Speech(Speech {
+                character: "B".to_owned(),
+                direction: vec![],
+                body: vec![Inline::Emphasis(vec![text("Good")]), text(" bye.")],
+            }),
+            Block::Direction { content: vec![Inline::Direction(vec![text("B sits.")])] },
+            Block::Heading { level: 4, content: vec![text("Note")] },
+        ]);
+    }
+
+    #[test]
+    fn build_play_with_monologue_and_switches() {
+        let s = r#"<!-- playscript-monologue-begin -->
+Alone. (sighs)
+<!-- playscript-monologue-end -->
+
+<!-- playscript-off -->
+
+A> Not a speech.
+
+- A> List items are ignored.
+"#;
+        let play = parse(s);
+        assert_eq!(play.acts[0].scenes[0].blocks, vec![
+            Block::Monologue {
+                content: vec![text("Alone. "), Inline::Direction(vec![text("sighs")])],
+            },
+            Block::Prose { content: vec![text("A> Not a speech.")] },
+        ]);
+        assert!(play.characters.is_empty());
+    }
+
+    #[test]
+    fn resolve_aliases_of_characters() {
+        let s = "<!-- playscript-cast\nFigaro (F)\n-->\n\nF> Hello.\nFigaro> Bye.";
+        let play = parse(s);
+        assert_eq!(play.characters, vec!["Figaro".to_owned()]);
+        assert!(play.speeches().all(|speech| speech.character == "Figaro"));
+    }
+
+    #[test]
+    fn build_play_with_custom_structure() {
+        let s = "## Akt I\n\nA> Hallo.\n\n## Szene 2\n\nB> Ja.\n\n## Anhang";
+        let structure = Structure::new()
+            .act_level(None)
+            .scene_level(None)
+            .act_names(vec!["Akt".to_owned()])
+            .scene_names(vec!["Szene".to_owned()]);
+        let options = Options::default().structure(structure);
+        let play = Play::from_events(Parser::new(s), &options, Params::default());
+        assert_eq!(play.acts.len(), 1);
+        assert_eq!(play.acts[0].title.as_deref(), Some("Akt I"));
+        let titles: Vec<_> = play.acts[0].scenes.iter()
+            .map(|scene| scene.title.as_deref())
+            .collect();
+        assert_eq!(titles, vec![None, Some("Szene 2")]);
+        assert_eq!(play.acts[0].scenes[1].blocks[1], Block::Heading {
+            level: 2,
+            content: vec![text("Anhang")],
+        });
+    }
+
+    #[test]
+    fn serialize_play_to_json() {
+        let play = parse("## Act\n\nA> Hi (waves)");
+        let json = serde_json::to_value(&play).unwrap();
+        let speech = &json["acts"][0]["scenes"][0]["blocks"][0];
+        assert_eq!(speech["type"], "speech");
+        assert_eq!(speech["character"], "A");
+        assert_eq!(speech["body"][0], serde_json::json!({"type": "text", "content": "Hi "}));
+        assert_eq!(speech["body"][1]["type"], "direction");
+
+        let decoded: Play = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, play);
+    }
+}
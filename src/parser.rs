@@ -1,6 +1,50 @@
 use std::marker::PhantomData;
 use pulldown_cmark::{Event, Tag};
 
+/// Delimiters of speech headings and directions.
+///
+/// ASCII `>` and `(`, `)` are always accepted. Full-width `＞` and `（`, `）` are accepted if
+/// `full_width` is set, and tortoise shell brackets `〔`, `〕` if `tortoise_shell` is set.
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub struct Delimiters {
+    pub full_width: bool,
+    pub tortoise_shell: bool,
+    /// Keeps brackets other than `(` and `)` in directions so that they are rendered as written.
+    pub preserve_brackets: bool,
+}
+
+impl Delimiters {
+    /// Delimiters for Japanese, which accept full-width `＞` and `（`, `）`.
+    pub fn japanese() -> Self {
+        Self {
+            full_width: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_angle(&self, c: char) -> bool {
+        c == '>' || (self.full_width && c == '＞')
+    }
+
+    pub fn is_open(&self, c: char) -> bool {
+        c == '(' || (self.full_width && c == '（') || (self.tortoise_shell && c == '〔')
+    }
+
+    pub fn is_close(&self, c: char) -> bool {
+        c == ')' || (self.full_width && c == '）') || (self.tortoise_shell && c == '〕')
+    }
+
+    /// Returns the close bracket which corresponds to an open bracket.
+    pub fn close_of(&self, open: char) -> Option<char> {
+        match open {
+            '(' => Some(')'),
+            '（' if self.full_width => Some('）'),
+            '〔' if self.tortoise_shell => Some('〕'),
+            _ => None,
+        }
+    }
+}
+
 /// An iterator which fuses when a paragraph end comes.
 #[derive(Debug)]
 pub struct FuseOnParagraphEnd<'a, I> {
@@ -50,11 +94,15 @@ where
 }
 
 /// Split events with speech starting line.
+///
+/// Only a text at the beginning of a line starts a speech.
 #[derive(Debug)]
 pub struct Speeches<'a, I> {
     iter: FuseOnParagraphEnd<'a, I>,
     is_first: bool,
+    at_line_head: bool,
     last: Option<Event<'a>>,
+    delimiters: Delimiters,
 }
 
 impl<'a, I> Speeches<'a, I>
@@ -62,10 +110,16 @@ where
     I: Iterator<Item=Event<'a>>,
 {
     pub fn new(paragraph: FuseOnParagraphEnd<'a, I>) -> Self {
+        Self::with_delimiters(paragraph, Delimiters::default())
+    }
+
+    pub fn with_delimiters(paragraph: FuseOnParagraphEnd<'a, I>, delimiters: Delimiters) -> Self {
         Self {
             iter: paragraph,
             is_first: true,
+            at_line_head: true,
             last: None,
+            delimiters,
         }
     }
 
@@ -88,8 +142,11 @@ where
         }
 
         while let Some(event) = self.iter.next() {
+            let at_line_head = self.at_line_head;
+            self.at_line_head = matches!(event, Event::SoftBreak | Event::HardBreak);
+
             match event {
-                Event::Text(s) if is_speech_start(s.as_ref()) => {
+                Event::Text(s) if at_line_head && is_speech_start_with(s.as_ref(), &self.delimiters) => {
                     if self.is_first {
                         speech.push(Event::Text(s));
                         self.is_first = false;
@@ -118,7 +175,7 @@ fn find_one_char(s: &str, pat: char) -> Option<usize> {
         None => return None,
     };
 
-    let after = &s[start+1..];
+    let after = &s[start+pat.len_utf8()..];
     if after.starts_with(pat) {
         None
     } else {
@@ -129,14 +186,27 @@ fn find_one_char(s: &str, pat: char) -> Option<usize> {
 /// Split speech heading and body.
 ///
 /// Speech format is `Character> speech`. This function splits at a first single right angle.
-/// If it has no right angle, or two or more right angles, it returns `None`.
+/// If it has no right angle, two or more right angles or nothing before the right angle,
+/// it returns `None`.
 pub fn split_speech_heading(s: &str) -> Option<(&str, &str)> {
-    find_one_char(s, '>')
-        .map(|pos| (&s[..pos], &s[pos+1..]))
+    split_speech_heading_with(s, &Delimiters::default())
+}
+
+/// Split speech heading and body at a right angle accepted by the delimiters.
+pub fn split_speech_heading_with<'s>(s: &'s str, delimiters: &Delimiters) -> Option<(&'s str, &'s str)> {
+    let angle = s.chars().find(|c| delimiters.is_angle(*c))?;
+
+    find_one_char(s, angle)
+        .filter(|&pos| !s[..pos].trim().is_empty())
+        .map(|pos| (&s[..pos], &s[pos+angle.len_utf8()..]))
 }
 
 pub fn is_speech_start(s: &str) -> bool {
-    find_one_char(s, '>').is_some()
+    is_speech_start_with(s, &Delimiters::default())
+}
+
+pub fn is_speech_start_with(s: &str, delimiters: &Delimiters) -> bool {
+    split_speech_heading_with(s, delimiters).is_some()
 }
 
 #[cfg(test)]
@@ -183,6 +253,14 @@ B> Hello!"#;
         assert_eq!(split_speech_heading("A (ddd)>"), Some(("A (ddd)", "")));
     }
 
+    #[test]
+    fn split_speech_line_with_full_width_angle() {
+        let delimiters = Delimiters::japanese();
+        assert_eq!(split_speech_heading_with("お蔦＞ 貴方。", &delimiters), Some(("お蔦", " 貴方。")));
+        assert_eq!(split_speech_heading_with("＞＞ 引用", &delimiters), None);
+        assert_eq!(split_speech_heading("お蔦＞ 貴方。"), None);
+    }
+
     #[test]
     fn split_normal_line() {
         assert_eq!(split_speech_heading("A xxx"), None);
@@ -258,4 +336,16 @@ A> *Good!*"#;
         ]));
         assert_eq!(it.next(), None);
     }
+
+    #[test]
+    fn speeches_iter_with_escaped_angles() {
+        let s = "A> Hello!\nx \\> y\n\\> z";
+        let mut it = make_speeches_iter(s);
+        assert_eq!(it.next(), Some(vec![
+                Event::Text("A> Hello!".into()), Event::SoftBreak,
+                Event::Text("x ".into()), Event::Text("> y".into()), Event::SoftBreak,
+                Event::Text("> z".into()),
+        ]));
+        assert_eq!(it.next(), None);
+    }
 }
@@ -0,0 +1,425 @@
+use std::convert::TryFrom;
+use std::path::Path;
+use mdbook::book::{Book, BookItem};
+use mdbook::errors::{Error, Result};
+use mdbook::preprocess::{Preprocessor, PreprocessorContext};
+use mdbook::Config;
+use pulldown_cmark::{Event, Parser};
+use crate::diagnostics::{Severity, lint};
+use crate::anchor::{IdScheme, slugify};
+use crate::interface::{MdPlayScriptBuilder, Options, Params, markdown_options};
+use crate::parser::Delimiters;
+
+/// An mdbook preprocessor which converts play scripts in every chapter.
+///
+/// It is configured by the table `[preprocessor.playscript]` in `book.toml`:
+///
+/// ```toml
+/// [preprocessor.playscript]
+/// language = "ja"
+/// replace-softbreaks-with = ""
+/// auto-softbreaks = false
+/// disabled-in-default = false
+/// title = "Title"
+/// subtitle = "Subtitle"
+/// authors = ["Author"]
+/// speech-ids = "scene"
+/// full-width-delimiters = true
+/// tortoise-shell-brackets = false
+/// preserve-brackets = false
+/// vertical = false
+/// act-level = 2
+/// scene-level = 3
+/// act-names = ["Acte"]
+/// scene-names = ["Scène"]
+/// ```
+///
+/// `title` and `authors` default to the ones in the `[book]` table.
+/// `speech-ids` is one of `counter`, `scene`, `character` and `hash`; see `anchor::IdScheme`.
+/// `full-width-delimiters`, `tortoise-shell-brackets` and `preserve-brackets` set
+/// `parser::Delimiters`; full-width delimiters are accepted by default for Japanese.
+/// `act-level`, `scene-level`, `act-names` and `scene-names` set `structure::Structure`;
+/// a level of 0 disables the units by levels, and `幕` and `場` are the names for Japanese.
+/// `auto-softbreaks` joins lines by a space only between non-CJK characters instead of
+/// replacing soft breaks with `replace-softbreaks-with`.
+#[derive(Debug,Default)]
+pub struct PlayScriptPreprocessor;
+
+impl PlayScriptPreprocessor {
+    pub fn new() -> Self {
+        PlayScriptPreprocessor
+    }
+}
+
+impl Preprocessor for PlayScriptPreprocessor {
+    fn name(&self) -> &str {
+        "playscript"
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let (options, params) = read_config(&ctx.config, self.name())?;
+        let mut error = None;
+
+        book.for_each_mut(|item| {
+            if error.is_some() {
+                return;
+            }
+
+            if let BookItem::Chapter(chapter) = item {
+                let path = chapter.path.as_deref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| chapter.name.clone());
+                for diagnostic in lint(&chapter.content, &options).iter() {
+                    match diagnostic.severity {
+                        Severity::Error => log::error!("{}:{}", path, diagnostic),
+                        Severity::Warning => log::warn!("{}:{}", path, diagnostic),
+                    }
+                }
+
+                let converted = convert_chapter(
+                    &chapter.content,
+                    options.clone(),
+                    params.clone(),
+                    chapter.path.as_deref(),
+                );
+                match converted {
+                    Ok(content) => chapter.content = content,
+                    Err(e) => {
+                        error.replace(e);
+                    },
+                }
+            }
+        });
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(book),
+        }
+    }
+
+    fn supports_renderer(&self, renderer: &str) -> bool {
+        renderer == "html"
+    }
+}
+
+fn config_str(config: &Config, name: &str, key: &str) -> Result<Option<String>> {
+    match config.get(&format!("preprocessor.{}.{}", name, key)) {
+        Some(value) => value.as_str()
+            .map(|s| Some(s.to_owned()))
+            .ok_or_else(|| Error::msg(format!("preprocessor.{}.{} must be a string", name, key))),
+        None => Ok(None),
+    }
+}
+
+fn config_u32(config: &Config, name: &str, key: &str) -> Result<Option<u32>> {
+    match config.get(&format!("preprocessor.{}.{}", name, key)) {
+        Some(value) => value.as_integer()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(Some)
+            .ok_or_else(|| Error::msg(format!("preprocessor.{}.{} must be a non-negative integer", name, key))),
+        None => Ok(None),
+    }
+}
+
+fn config_strings(config: &Config, name: &str, key: &str) -> Result<Option<Vec<String>>> {
+    let key = format!("preprocessor.{}.{}", name, key);
+    match config.get(&key) {
+        Some(value) => {
+            let array = value.as_array()
+                .ok_or_else(|| Error::msg(format!("{} must be an array", key)))?;
+            array.iter()
+                .map(|v| v.as_str()
+                    .map(|s| s.to_owned())
+                    .ok_or_else(|| Error::msg(format!("{} must be an array of strings", key))))
+                .collect::<Result<Vec<_>>>()
+                .map(Some)
+        },
+        None => Ok(None),
+    }
+}
+
+fn config_bool(config: &Config, name: &str, key: &str) -> Result<Option<bool>> {
+    match config.get(&format!("preprocessor.{}.{}", name, key)) {
+        Some(value) => value.as_bool()
+            .map(Some)
+            .ok_or_else(|| Error::msg(format!("preprocessor.{}.{} must be a boolean", name, key))),
+        None => Ok(None),
+    }
+}
+
+/// Reads `Options` and `Params` from the preprocessor table of `book.toml`.
+pub fn read_config(config: &Config, name: &str) -> Result<(Options, Params)> {
+    let language = config_str(config, name, "language")?
+        .or_else(|| config.book.language.clone());
+    let mut options = match language.as_deref() {
+        Some("ja") => Options::default_ja(),
+        _ => Options::default(),
+    };
+
+    if let Some(s) = config_str(config, name, "replace-softbreaks-with")? {
+        options = options.replace_softbreaks_with(Some(s));
+    }
+
+    if let Some(val) = config_bool(config, name, "auto-softbreaks")? {
+        options = options.auto_softbreaks(val);
+    }
+
+    if let Some(val) = config_bool(config, name, "disabled-in-default")? {
+        options = options.disabled_in_default(val);
+    }
+
+    if let Some(s) = config_str(config, name, "speech-ids")? {
+        let scheme = s.parse::<IdScheme>()
+            .map_err(|e| Error::msg(format!("preprocessor.{}.speech-ids: {}", name, e)))?;
+        options = options.id_scheme(scheme);
+    }
+
+    let delimiters = options.delimiters;
+    let delimiters = Delimiters {
+        full_width: config_bool(config, name, "full-width-delimiters")?.unwrap_or(delimiters.full_width),
+        tortoise_shell: config_bool(config, name, "tortoise-shell-brackets")?.unwrap_or(delimiters.tortoise_shell),
+        preserve_brackets: config_bool(config, name, "preserve-brackets")?.unwrap_or(delimiters.preserve_brackets),
+    };
+    options = options.delimiters(delimiters);
+
+    if let Some(val) = config_bool(config, name, "vertical")? {
+        options = options.vertical(val);
+    }
+
+    let mut structure = options.structure.clone();
+    if let Some(level) = config_u32(config, name, "act-level")? {
+        structure = structure.act_level(Some(level).filter(|&level| level > 0));
+    }
+    if let Some(level) = config_u32(config, name, "scene-level")? {
+        structure = structure.scene_level(Some(level).filter(|&level| level > 0));
+    }
+    if let Some(names) = config_strings(config, name, "act-names")? {
+        structure = structure.act_names(names);
+    }
+    if let Some(names) = config_strings(config, name, "scene-names")? {
+        structure = structure.scene_names(names);
+    }
+    options = options.structure(structure);
+
+    let authors = config_strings(config, name, "authors")?
+        .unwrap_or_else(|| config.book.authors.clone());
+
+    let params = Params {
+        title: config_str(config, name, "title")?
+            .or_else(|| config.book.title.clone()),
+        subtitle: config_str(config, name, "subtitle")?,
+        authors,
+    };
+
+    Ok((options, params))
+}
+
+/// Converts play scripts in a chapter into Markdown with embedded HTML.
+///
+/// The path of the chapter is added to the HTML classes of speeches and to the prefix of
+/// their anchor IDs, so that IDs do not collide when chapters are rendered onto one page.
+pub fn convert_chapter(content: &str, options: Options, params: Params, path: Option<&Path>) -> Result<String> {
+    let parser = Parser::new_ext(content, markdown_options());
+    let mut mdplay = MdPlayScriptBuilder::new()
+        .options(options)
+        .params(params)
+        .build(parser);
+
+    if let Some(path) = path.and_then(|p| p.to_str()) {
+        mdplay.renderer_mut().speech_classes.add(path);
+    }
+    if let Some(stem) = path.and_then(|p| p.with_extension("").to_str().map(slugify)) {
+        mdplay.renderer_mut().speech_ids.get_mut().set_prefix(&format!("{}-D", stem));
+    }
+
+    let mut buf = String::new();
+    pulldown_cmark_to_cmark::cmark(render_html_blocks(mdplay).into_iter(), &mut buf, None)
+        .map_err(|_| Error::msg("failed to write a chapter in Markdown"))?;
+
+    Ok(buf)
+}
+
+/// Returns the number of elements opened minus the number of elements closed in an HTML
+/// fragment. Comments, void elements and self-closing tags are not counted.
+fn html_depth(s: &str) -> i32 {
+    const VOID_ELEMENTS: &[&str] = &["area", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "wbr"];
+    let mut depth = 0;
+
+    for (pos, _) in s.match_indices('<') {
+        let tag = &s[pos+1..];
+        let end = tag.find('>').unwrap_or(tag.len());
+        if tag.starts_with('/') {
+            depth -= 1;
+        } else if tag.starts_with(|c: char| c.is_ascii_alphabetic()) && !tag[..end].ends_with('/') {
+            let name = tag.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or("");
+            if !VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                depth += 1;
+            }
+        }
+    }
+
+    depth
+}
+
+/// Renders the elements which the renderer makes of speeches and directives into single HTML
+/// events.
+///
+/// mdbook does not parse Markdown in HTML blocks, so inline Markdown in speeches would be left
+/// as it is if the events were written as Markdown.
+fn render_html_blocks<'a, I>(iter: I) -> Vec<Event<'a>>
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    let mut events = Vec::new();
+    let mut block = Vec::new();
+    let mut depth = 0;
+    // Inline HTML in Markdown blocks is passed through.
+    let mut nesting = 0;
+
+    for event in iter {
+        if block.is_empty() {
+            match &event {
+                Event::Html(s) if nesting == 0 && html_depth(s) > 0 => {},
+                Event::Start(_) => nesting += 1,
+                Event::End(_) => nesting -= 1,
+                _ => {},
+            }
+            if nesting > 0 || !matches!(event, Event::Html(_)) {
+                events.push(event);
+                continue;
+            }
+        }
+
+        if let Event::Html(s) = &event {
+            depth += html_depth(s);
+        }
+        block.push(event);
+        if depth <= 0 {
+            events.push(render_html_block(block.drain(..)));
+            depth = 0;
+        }
+    }
+
+    if !block.is_empty() {
+        events.push(render_html_block(block.into_iter()));
+    }
+
+    events
+}
+
+/// Renders events into an HTML block, which is followed by a blank line to end it.
+fn render_html_block<'a, I>(iter: I) -> Event<'a>
+where
+    I: Iterator<Item=Event<'a>>,
+{
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, iter);
+    html.truncate(html.trim_end().len());
+    html.push('\n');
+
+    Event::Html(html.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use crate::structure::Structure;
+
+    #[test]
+    fn read_config_from_book_table() {
+        let config = Config::from_str(r#"
+[book]
+title = "Le Mariage de Figaro"
+authors = ["Beaumarchais"]
+language = "ja"
+
+[preprocessor.playscript]
+subtitle = "Acte I"
+"#).unwrap();
+        let (options, params) = read_config(&config, "playscript").unwrap();
+        assert_eq!(options, Options::default_ja());
+        assert_eq!(params, Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: Some("Acte I".to_owned()),
+            authors: vec!["Beaumarchais".to_owned()],
+        });
+    }
+
+    #[test]
+    fn read_config_overriding_book_table() {
+        let config = Config::from_str(r#"
+[book]
+title = "Book"
+authors = ["Editor"]
+
+[preprocessor.playscript]
+title = "Play"
+authors = ["Author"]
+replace-softbreaks-with = "/"
+disabled-in-default = true
+speech-ids = "character"
+tortoise-shell-brackets = true
+scene-level = 0
+scene-names = ["Scène"]
+"#).unwrap();
+        let (options, params) = read_config(&config, "playscript").unwrap();
+        let expected = Options::default()
+            .replace_softbreaks_with(Some("/".to_owned()))
+            .disabled_in_default(true)
+            .id_scheme(IdScheme::Character)
+            .delimiters(Delimiters {
+                tortoise_shell: true,
+                ..Delimiters::default()
+            })
+            .structure(Structure::new()
+                .scene_level(None)
+                .scene_names(vec!["Scène".to_owned()]));
+        assert_eq!(options, expected);
+        assert_eq!(params.title, Some("Play".to_owned()));
+        assert_eq!(params.authors, vec!["Author".to_owned()]);
+    }
+
+    #[test]
+    fn read_config_with_wrong_type() {
+        let config = Config::from_str(r#"
+[preprocessor.playscript]
+disabled-in-default = "yes"
+"#).unwrap();
+        assert!(read_config(&config, "playscript").is_err());
+
+        let config = Config::from_str(r#"
+[preprocessor.playscript]
+act-level = -1
+"#).unwrap();
+        assert!(read_config(&config, "playscript").is_err());
+    }
+
+    #[test]
+    fn convert_chapter_with_path_class() {
+        let output = convert_chapter(
+            "A> Hello!",
+            Options::default(),
+            Params::default(),
+            Some(Path::new("act1/scene1.md")),
+        ).unwrap();
+        assert!(output.contains(r#"<div class="speech act1%2fscene1%2emd">"#));
+        assert!(output.contains(r#"<h5 id="act1-scene1-D0">"#));
+        assert!(output.contains(r#"<span class="character">A</span>"#));
+    }
+
+    #[test]
+    fn convert_chapter_rendering_inline_markdown() {
+        let output = convert_chapter("A> *em* `c<d`\n\nText *em*", Options::default(), Params::default(), None).unwrap();
+        assert!(output.contains("<em>em</em> <code>c&lt;d</code>"));
+        assert!(output.contains("</div>\n\nText *em*"));
+    }
+
+    #[test]
+    fn count_depth_of_html() {
+        assert_eq!(html_depth(r#"<div class="speech">"#), 1);
+        assert_eq!(html_depth("<p><span>"), 2);
+        assert_eq!(html_depth("</span></a>"), -2);
+        assert_eq!(html_depth("<br /><img src=\"a.png\"><!-- playscript-monologue-begin -->"), 0);
+    }
+}
@@ -1,6 +1,88 @@
 use std::cell::RefCell;
-use pulldown_cmark::Event;
+use std::str::FromStr;
+use pulldown_cmark::{Event, Tag};
+use crate::interface::Params;
 use crate::speech::{Speech, Heading, Direction, Inline};
+use crate::cast::CastListEntry;
+use crate::stats::{format_minutes, is_cjk};
+use crate::anchor::{SpeechIds, SpeechKey};
+use crate::ruby::{expand_ruby, strip_ruby};
+use crate::vertical::tate_chu_yoko;
+
+/// A renderer which converts parsed play scripts into events.
+///
+/// `MdPlayScript` calls the methods of a renderer for speeches, monologues, normal paragraphs
+/// and title directives. The other events are passed through.
+pub trait Renderer {
+    fn render_speech<'a>(&self, speech: Speech<'a>, events: &mut Vec<Event<'a>>);
+
+    fn render_monologue<'a>(&self, body: Vec<Inline<'a>>, events: &mut Vec<Event<'a>>);
+
+    /// Renders a paragraph which is neither a speech nor a monologue.
+    /// The events do not contain the start and the end of the paragraph.
+    fn render_paragraph<'a>(&self, events: Vec<Event<'a>>, output: &mut Vec<Event<'a>>);
+
+    /// Renders the title for the directive `playscript-title`.
+    fn render_title<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        if let Some(title) = params.title.as_ref().cloned() {
+            events.push(Event::Start(Tag::Heading(1)));
+            events.push(Event::Text(title.into()));
+            events.push(Event::End(Tag::Heading(1)));
+        }
+    }
+
+    /// Renders the subtitle for the directive `playscript-subtitle`.
+    fn render_subtitle<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        if let Some(subtitle) = params.subtitle.as_ref().cloned() {
+            events.push(Event::Start(Tag::Heading(2)));
+            events.push(Event::Text(subtitle.into()));
+            events.push(Event::End(Tag::Heading(2)));
+        }
+    }
+
+    /// Renders the authors for the directive `playscript-authors`.
+    fn render_authors<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        for author in params.authors.iter().cloned() {
+            events.push(Event::Start(Tag::Paragraph));
+            events.push(Event::Text(author.into()));
+            events.push(Event::End(Tag::Paragraph));
+        }
+    }
+
+    /// Renders the list of characters for the directive `playscript-cast-list`.
+    fn render_cast_list<'a>(&self, entries: &[CastListEntry], events: &mut Vec<Event<'a>>) {
+        if entries.is_empty() {
+            return;
+        }
+
+        events.push(Event::Start(Tag::List(None)));
+        for entry in entries.iter() {
+            events.push(Event::Start(Tag::Item));
+            events.push(Event::Text(entry.name.clone().into()));
+            if let Some(description) = entry.description.as_ref() {
+                events.push(Event::Text(format!(", {}", description).into()));
+            }
+            events.push(Event::End(Tag::Item));
+        }
+        events.push(Event::End(Tag::List(None)));
+    }
+
+    /// Notifies the renderer that the following speeches belong to the act and the scene.
+    fn enter_scene(&self, _act: Option<&str>, _scene: Option<&str>) {
+    }
+
+    /// Notifies the renderer of the speeches read ahead for the cast list, which is rendered
+    /// next.
+    fn read_ahead(&self, _speeches: &[SpeechKey]) {
+    }
+
+    /// Renders the estimated running time after a scene heading.
+    fn render_running_time<'a>(&self, minutes: f64, events: &mut Vec<Event<'a>>) {
+        events.push(Event::Start(Tag::Paragraph));
+        events.push(Event::Text(format_minutes(minutes).into()));
+        events.push(Event::End(Tag::Paragraph));
+    }
+}
 
 #[derive(Debug)]
 pub struct HtmlClasses {
@@ -73,14 +155,88 @@ impl From<&[&str]> for HtmlClasses {
     }
 }
 
+/// How the lines of characters are hidden in the line-learning mode.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Concealment {
+    /// The body of a speech is collapsed into a `<details>` element revealed by a click.
+    Collapse,
+    /// Each word of the body is replaced by its first letter: `T, F, v m p c.`
+    FirstLetters,
+}
+
+impl FromStr for Concealment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "collapse" => Ok(Concealment::Collapse),
+            "first-letters" => Ok(Concealment::FirstLetters),
+            _ => Err(format!("unknown concealment: {}", s)),
+        }
+    }
+}
+
+/// The line-learning mode, which hides the lines of characters so that an actor can learn
+/// them with the other characters' lines as cues.
+#[derive(Debug,Clone,PartialEq)]
+pub struct LineLearning {
+    pub characters: Vec<String>,
+    pub concealment: Concealment,
+}
+
+impl LineLearning {
+    pub fn new(characters: Vec<String>, concealment: Concealment) -> Self {
+        Self {
+            characters,
+            concealment,
+        }
+    }
+
+    fn hides(&self, character: &str) -> bool {
+        self.characters.iter().any(|name| name == character)
+    }
+}
+
+/// Replaces each word by its first letter. Punctuation and spaces are kept.
+///
+/// A run of CJK characters is also replaced by its first character.
+pub fn first_letters(s: &str) -> String {
+    let mut letters = String::new();
+    let mut in_word = false;
+
+    for c in s.chars() {
+        if c.is_alphanumeric() {
+            if !in_word {
+                letters.push(c);
+            }
+            in_word = true;
+        } else if in_word && (c == '\'' || c == '’') {
+            // Apostrophes in a word such as `we've`.
+        } else {
+            letters.push(c);
+            in_word = false;
+        }
+    }
+
+    letters
+}
+
 #[derive(Debug)]
 pub struct HtmlRenderer {
     pub speech_classes: HtmlClasses,
     pub character_class: &'static str,
     pub direction_class: &'static str,
     pub heading_anchor_class: &'static str,
-    pub heading_id_counter: RefCell<usize>,
+    pub speech_ids: RefCell<SpeechIds>,
     pub replace_softbreak: Option<String>,
+    /// Replaces soft breaks by a space only between non-CJK characters, instead of
+    /// `replace_softbreak`.
+    pub auto_softbreak: bool,
+    pub line_learning: Option<LineLearning>,
+    /// Renders ruby annotations in speeches into `<ruby>` elements.
+    pub ruby: bool,
+    /// Renders for vertical writing; see the module `vertical`.
+    pub vertical: bool,
 }
 
 impl Default for HtmlRenderer {
@@ -91,63 +247,96 @@ impl Default for HtmlRenderer {
             character_class: "character",
             direction_class: "direction",
             heading_anchor_class: "header",
-            heading_id_counter: RefCell::new(0),
+            speech_ids: RefCell::new(SpeechIds::default()),
             replace_softbreak: Some(" ".to_owned()),
+            auto_softbreak: false,
+            line_learning: None,
+            ruby: false,
+            vertical: false,
         }
     }
 }
 
 impl HtmlRenderer {
+    /// Forgets the anchor IDs given so far, so that the next speech is the first one of
+    /// a new document.
+    pub fn reset_ids(&self) {
+        self.speech_ids.borrow_mut().reset();
+    }
+
+    fn replace_softbreaks<'a>(&self, inlines: &mut Vec<Inline<'a>>) {
+        if self.auto_softbreak {
+            join_softbreaks(inlines);
+        } else {
+            replace_softbreaks(inlines, self.replace_softbreak.as_ref());
+        }
+    }
+
     fn render_speech_begin<'a>(&self, events: &mut Vec<Event<'a>>) {
         let div_start = format!("<div class=\"{}\">", self.speech_classes.as_str());
-        
+
         events.push(Event::Html(div_start.into()));
     }
 
-    fn render_speech_end<'a>(&self, events: &mut Vec<Event<'a>>) {
-        let div_end = "</div>";
-
-        events.push(Event::Html(div_end.into()));
+    /// Converts ruby annotations in the events rendered from `start` if `ruby` is enabled.
+    fn render_ruby<'a>(&self, start: usize, events: &mut Vec<Event<'a>>) {
+        if self.ruby {
+            let rendered = events.split_off(start);
+            events.extend(expand_ruby(rendered));
+        }
     }
 
-    pub fn render_speech<'a>(&self, speech: Speech<'a>, events: &mut Vec<Event<'a>>) {
-        self.render_speech_begin(events);
+    /// Sets numerals in the events rendered from `start` horizontally if `vertical` is enabled.
+    fn render_tate_chu_yoko<'a>(&self, start: usize, events: &mut Vec<Event<'a>>) {
+        if self.vertical {
+            let rendered = events.split_off(start);
+            events.extend(tate_chu_yoko(rendered));
+        }
+    }
 
-        self.render_heading(speech.heading, events);
-        self.render_body(speech.body, events);
+    fn render_hidden_speech_begin<'a>(&self, events: &mut Vec<Event<'a>>) {
+        let div_start = format!("<div class=\"{} line-learning\">", self.speech_classes.as_str());
 
-        self.render_speech_end(events);
+        events.push(Event::Html(div_start.into()));
     }
 
-    pub fn render_monologue<'a>(&self, body: Vec<Inline<'a>>, events: &mut Vec<Event<'a>>) {
-        self.render_speech_begin(events);
-        self.render_body(body, events);
-        self.render_speech_end(events);
-    }
+    fn render_speech_end<'a>(&self, events: &mut Vec<Event<'a>>) {
+        let div_end = "</div>";
 
-    pub fn render_heading<'a>(&self, heading: Heading<'a>, events: &mut Vec<Event<'a>>) {
-        let mut counter = self.heading_id_counter.borrow_mut();
+        events.push(Event::Html(div_end.into()));
+    }
 
-        let h_start = format!(r#"<h5 id="D{id}">"#,
-            id = counter,
+    pub fn render_heading<'a>(&self, heading: Heading<'a>, id: &str, events: &mut Vec<Event<'a>>) {
+        let h_start = format!(r#"<h5 id="{id}">"#,
+            id = id,
         );
-        let a_start = format!(r##"<a class="{class}" href="#D{id}">"##,
+        let a_start = format!(r##"<a class="{class}" href="#{id}">"##,
             class = self.heading_anchor_class,
-            id = counter,
+            id = id,
         );
         let span_start = format!(r#"<span class="{}">"#, self.character_class);
         let span_end = "</span>";
         let a_end = "</a>";
         let h_end = "</h5>";
 
-        *counter = *counter + 1;
+        // In vertical text, the direction is read before the character as a stage direction.
+        let direction = if self.vertical {
+            if !heading.direction.0.is_empty() {
+                events.push(Event::Html("<div class=\"heading-direction\">".into()));
+                self.render_direction(heading.direction, false, events);
+                events.push(Event::Html("</div>".into()));
+            }
+            Direction::new()
+        } else {
+            heading.direction
+        };
 
         events.push(Event::Html(h_start.into()));
         events.push(Event::Html(a_start.into()));
         events.push(Event::Html(span_start.into()));
         events.push(Event::Text(heading.character));
         events.push(Event::Html(span_end.into()));
-        self.render_direction(heading.direction, false, events);
+        self.render_direction(direction, false, events);
         events.push(Event::Html(a_end.into()));
         events.push(Event::Html(h_end.into()));
     }
@@ -160,7 +349,13 @@ impl HtmlRenderer {
             return;
         }
 
-        let span_begin = format!("<span class=\"{}\">", self.direction_class);
+        // Directions which keep their brackets are not bracketed again by stylesheets.
+        let bracketed = matches!(direction.first(), Some(Event::Text(s)) if s.starts_with(&['（', '〔'][..]));
+        let span_begin = if bracketed {
+            format!("<span class=\"{} bracketed\">", self.direction_class)
+        } else {
+            format!("<span class=\"{}\">", self.direction_class)
+        };
         let span_end = "</span>";
 
         events.push(Event::Html(span_begin.into()));
@@ -192,7 +387,7 @@ impl HtmlRenderer {
         let mut event_count = 0usize;
 
         let mut body = body;
-        replace_softbreaks(&mut body, self.replace_softbreak.as_ref());
+        self.replace_softbreaks(&mut body);
 
         events.push(Event::Html("<p>".into()));
 
@@ -242,7 +437,7 @@ impl HtmlRenderer {
             .map(|e| Inline::Event(e))
             .collect();
 
-        replace_softbreaks(&mut events, self.replace_softbreak.as_ref());
+        self.replace_softbreaks(&mut events);
 
         for e in events.into_iter() {
             match e {
@@ -255,6 +450,170 @@ impl HtmlRenderer {
     }
 }
 
+impl Renderer for HtmlRenderer {
+    fn render_speech<'a>(&self, speech: Speech<'a>, events: &mut Vec<Event<'a>>) {
+        let start = events.len();
+        let concealment = self.line_learning.as_ref()
+            .filter(|learning| learning.hides(speech.heading.character.as_ref()))
+            .map(|learning| learning.concealment);
+
+        match concealment {
+            Some(_) => self.render_hidden_speech_begin(events),
+            None => self.render_speech_begin(events),
+        }
+
+        let id = self.speech_ids.borrow_mut()
+            .next_id(&strip_ruby(&speech.heading.character), &body_text(&speech.body));
+        self.render_heading(speech.heading, &id, events);
+
+        match concealment {
+            Some(Concealment::Collapse) => {
+                events.push(Event::Html("<details class=\"line-learning\"><summary>…</summary>".into()));
+                self.render_body(speech.body, events);
+                events.push(Event::Html("</details>".into()));
+            },
+            Some(Concealment::FirstLetters) => {
+                let body = speech.body.into_iter()
+                    .map(|inline| match inline {
+                        Inline::Event(Event::Text(s)) => Inline::Event(Event::Text(first_letters(&s).into())),
+                        inline => inline,
+                    })
+                    .collect();
+                self.render_body(body, events);
+            },
+            None => {
+                self.render_body(speech.body, events);
+            },
+        }
+
+        self.render_speech_end(events);
+        self.render_ruby(start, events);
+        self.render_tate_chu_yoko(start, events);
+    }
+
+    fn render_monologue<'a>(&self, body: Vec<Inline<'a>>, events: &mut Vec<Event<'a>>) {
+        let start = events.len();
+        self.render_speech_begin(events);
+        self.render_body(body, events);
+        self.render_speech_end(events);
+        self.render_ruby(start, events);
+        self.render_tate_chu_yoko(start, events);
+    }
+
+    fn render_paragraph<'a>(&self, events: Vec<Event<'a>>, output: &mut Vec<Event<'a>>) {
+        let start = output.len();
+        output.push(Event::Start(Tag::Paragraph));
+        self.render_events(events, output);
+        output.push(Event::End(Tag::Paragraph));
+        self.render_tate_chu_yoko(start, output);
+    }
+
+    fn render_title<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        let p_start = "<h1 class=\"cover-title\">";
+        let p_end = "</h1>";
+
+        if let Some(content) = params.title.as_ref().cloned() {
+            events.push(Event::Html(p_start.into()));
+            events.push(Event::Text(content.into()));
+            events.push(Event::Html(p_end.into()));
+        }
+    }
+
+    fn render_subtitle<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        let p_start = "<h2 class=\"cover-title\">";
+        let p_end = "</h2>";
+
+        if let Some(content) = params.subtitle.as_ref().cloned() {
+            events.push(Event::Html(p_start.into()));
+            events.push(Event::Text(content.into()));
+            events.push(Event::Html(p_end.into()));
+        }
+    }
+
+    fn render_authors<'a>(&self, params: &Params, events: &mut Vec<Event<'a>>) {
+        let div_start = "<div class=\"authors\">";
+        let div_end = "</div>";
+        let p_start = "<p class=\"cover-author\">";
+        let p_end = "</p>";
+
+        if params.authors.is_empty() {
+            return;
+        }
+
+        events.push(Event::Html(div_start.into()));
+
+        for author in params.authors.iter().cloned() {
+            events.push(Event::Html(p_start.into()));
+            events.push(Event::Text(author.into()));
+            events.push(Event::Html(p_end.into()));
+        }
+
+        events.push(Event::Html(div_end.into()));
+    }
+
+    /// Renders the list of characters. Each name links to the heading of the first speech.
+    fn render_cast_list<'a>(&self, entries: &[CastListEntry], events: &mut Vec<Event<'a>>) {
+        let ul_start = "<ul class=\"cast-list\">";
+        let ul_end = "</ul>";
+        let span_start = format!(r#"<span class="{}">"#, self.character_class);
+
+        if entries.is_empty() {
+            return;
+        }
+
+        events.push(Event::Html(ul_start.into()));
+
+        for entry in entries.iter() {
+            let id = self.speech_ids.borrow_mut()
+                .first_speech_id(&entry.name, entry.first_speech);
+            let li_start = format!(r##"<li><a class="{class}" href="#{id}">"##,
+                class = self.heading_anchor_class,
+                id = id,
+            );
+
+            events.push(Event::Html(li_start.into()));
+            events.push(Event::Html(span_start.clone().into()));
+            events.push(Event::Text(entry.name.clone().into()));
+            events.push(Event::Html("</span></a>".into()));
+            if let Some(description) = entry.description.as_ref() {
+                events.push(Event::Html("<span class=\"description\">".into()));
+                events.push(Event::Text(description.clone().into()));
+                events.push(Event::Html("</span>".into()));
+            }
+            events.push(Event::Html("</li>".into()));
+        }
+
+        events.push(Event::Html(ul_end.into()));
+    }
+
+    fn enter_scene(&self, act: Option<&str>, scene: Option<&str>) {
+        self.speech_ids.borrow_mut().enter_scene(act, scene);
+    }
+
+    fn read_ahead(&self, speeches: &[SpeechKey]) {
+        self.speech_ids.borrow_mut().reserve(speeches);
+    }
+
+    fn render_running_time<'a>(&self, minutes: f64, events: &mut Vec<Event<'a>>) {
+        let p_start = "<p class=\"running-time\">";
+        let p_end = "</p>";
+
+        events.push(Event::Html(p_start.into()));
+        events.push(Event::Text(format_minutes(minutes).into()));
+        events.push(Event::Html(p_end.into()));
+    }
+}
+
+/// Concatenates the texts in the body of a speech.
+pub(crate) fn body_text(body: &[Inline]) -> String {
+    body.iter()
+        .filter_map(|inline| match inline {
+            Inline::Event(Event::Text(s)) | Inline::Event(Event::Code(s)) => Some(s.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
 fn trim_end_of_last<'a>(events: &mut Vec<Event<'a>>) {
     match events.pop() {
         Some(Event::Text(s)) => {
@@ -285,11 +644,61 @@ pub fn replace_softbreaks<'a>(inlines: &mut Vec<Inline<'a>>, s: Option<&String>)
     }
 }
 
+/// Returns `true` if lines can be joined without a space after or before the character.
+fn is_cjk_or_punct(c: char) -> bool {
+    is_cjk(c) || ('\u{3000}'..='\u{303f}').contains(&c) || ('\u{ff00}'..='\u{ffef}').contains(&c)
+}
+
+fn text_of<'e>(event: &'e Event) -> Option<&'e str> {
+    match event {
+        Event::Text(s) | Event::Code(s) => Some(s.as_ref()),
+        _ => None,
+    }
+}
+
+fn first_char(inline: &Inline) -> Option<char> {
+    match inline {
+        Inline::Event(event) => text_of(event).and_then(|s| s.chars().next()),
+        Inline::Direction(direction) => direction.0.iter()
+            .find_map(|event| text_of(event).and_then(|s| s.chars().next())),
+    }
+}
+
+fn last_char(inline: &Inline) -> Option<char> {
+    match inline {
+        Inline::Event(event) => text_of(event).and_then(|s| s.chars().last()),
+        Inline::Direction(direction) => direction.0.iter()
+            .rev()
+            .find_map(|event| text_of(event).and_then(|s| s.chars().last())),
+    }
+}
+
+/// Replaces soft breaks according to the characters on both sides of them.
+///
+/// A soft break becomes a space between non-CJK characters, such as Latin words, and is
+/// removed next to CJK characters and punctuations or whitespaces.
+pub fn join_softbreaks(inlines: &mut [Inline<'_>]) {
+    for index in 0..inlines.len() {
+        if inlines[index] != Inline::Event(Event::SoftBreak) {
+            continue;
+        }
+
+        let before = inlines[..index].iter().rev().find_map(last_char);
+        let after = inlines[index+1..].iter().find_map(first_char);
+        let s = match (before, after) {
+            (Some(before), Some(after)) if !is_cjk_or_punct(before) && !is_cjk_or_punct(after)
+                && !before.is_whitespace() && !after.is_whitespace() => " ",
+            _ => "",
+        };
+        inlines[index] = Inline::Event(Event::Text(s.into()));
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::anchor::IdScheme;
     use pretty_assertions::assert_eq;
-    use pulldown_cmark::Tag;
 
     #[test]
     fn encode_html_class_with_puncts() {
@@ -362,7 +771,7 @@ mod test {
             Event::Html("</h5>".into()),
         ];
         let mut result = Vec::new();
-        HtmlRenderer::default().render_heading(input, &mut result);
+        HtmlRenderer::default().render_heading(input, "D0", &mut result);
         assert_eq!(result, expected);
     }
 
@@ -385,10 +794,62 @@ mod test {
             Event::Html("</h5>".into()),
         ];
         let mut result = Vec::new();
-        HtmlRenderer::default().render_heading(input, &mut result);
+        HtmlRenderer::default().render_heading(input, "D0", &mut result);
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn render_heading_with_direction_in_vertical_text() {
+        let input = Heading {
+            character: "お蔦".into(),
+            direction: Direction(vec![Event::Text("振返って".into())]),
+        };
+        let expected = vec![
+            Event::Html(r#"<div class="heading-direction">"#.into()),
+            Event::Html(r#"<span class="direction">"#.into()),
+            Event::Text("振返って".into()),
+            Event::Html("</span>".into()),
+            Event::Html("</div>".into()),
+            Event::Html(r#"<h5 id="D0">"#.into()),
+            Event::Html(r##"<a class="header" href="#D0">"##.into()),
+            Event::Html(r#"<span class="character">"#.into()),
+            Event::Text("お蔦".into()),
+            Event::Html("</span>".into()),
+            Event::Html("</a>".into()),
+            Event::Html("</h5>".into()),
+        ];
+        let renderer = HtmlRenderer {
+            vertical: true,
+            ..Default::default()
+        };
+        let mut result = Vec::new();
+        renderer.render_heading(input, "D0", &mut result);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn join_softbreaks_by_script() {
+        let mut inlines = vec![
+            Inline::Event(Event::Text("Il a dit".into())),
+            Inline::Event(Event::SoftBreak),
+            Inline::Event(Event::Text("« Bonjour ».".into())),
+            Inline::Event(Event::SoftBreak),
+            Inline::Event(Event::Text("それから".into())),
+            Inline::Event(Event::SoftBreak),
+            Inline::Direction(Direction(vec![Event::Text("笑う".into())])),
+            Inline::Event(Event::SoftBreak),
+            Inline::Event(Event::Text("Merci".into())),
+        ];
+        join_softbreaks(&mut inlines);
+        let texts: Vec<String> = inlines.iter()
+            .filter_map(|inline| match inline {
+                Inline::Event(Event::Text(s)) => Some(s.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["Il a dit", " ", "« Bonjour ».", "", "それから", "", "", "Merci"]);
+    }
+
     #[test]
     fn render_body_to_html() {
         let input = vec![
@@ -467,4 +928,66 @@ mod test {
         HtmlRenderer::default().render_body(input, &mut result);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn render_speeches_with_scene_ids() {
+        let renderer = HtmlRenderer {
+            speech_ids: RefCell::new(SpeechIds::new(IdScheme::Scene)),
+            ..Default::default()
+        };
+        let speech = |character: &'static str| Speech {
+            heading: Heading {
+                character: character.into(),
+                direction: Direction::new(),
+            },
+            body: vec![Inline::Event(Event::Text("Hello!".into()))],
+        };
+        let mut result = Vec::new();
+        renderer.enter_scene(None, Some("Scene 1"));
+        renderer.render_speech(speech("A"), &mut result);
+        renderer.reset_ids();
+        renderer.enter_scene(None, Some("Scene 1"));
+        renderer.render_speech(speech("B"), &mut result);
+        let ids: Vec<_> = result.iter()
+            .filter_map(|event| match event {
+                Event::Html(s) if s.starts_with("<h5") => Some(s.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec![r#"<h5 id="D-scene-1-0">"#, r#"<h5 id="D-scene-1-0">"#]);
+    }
+
+    #[test]
+    fn replace_words_with_first_letters() {
+        assert_eq!(first_letters("Tiens, Figaro, voilà mon petit chapeau."), "T, F, v m p c.");
+        assert_eq!(first_letters("We've got 19 feet."), "W g 1 f.");
+        assert_eq!(first_letters("早瀬さん、切れるの？"), "早、切？");
+    }
+
+    #[test]
+    fn hide_lines_of_character() {
+        let renderer = HtmlRenderer {
+            line_learning: Some(LineLearning::new(vec!["A".to_owned()], Concealment::FirstLetters)),
+            ..Default::default()
+        };
+        let speech = |character: &'static str| Speech {
+            heading: Heading {
+                character: character.into(),
+                direction: Direction::new(),
+            },
+            body: vec![
+                Inline::Event(Event::Text("Hello world! ".into())),
+                Inline::Direction(Direction(vec![Event::Text("waves".into())])),
+            ],
+        };
+        let mut result = Vec::new();
+        renderer.render_speech(speech("A"), &mut result);
+        renderer.render_speech(speech("B"), &mut result);
+        let mut buf = String::new();
+        pulldown_cmark::html::push_html(&mut buf, result.into_iter());
+        assert!(buf.starts_with(r#"<div class="speech line-learning"><h5 id="D0">"#));
+        assert!(buf.contains(r#"<p><span>H w!</span><span class="direction">waves</span></p>"#));
+        assert!(buf.contains(r#"<div class="speech"><h5 id="D1">"#));
+        assert!(buf.contains(r#"<p><span>Hello world!</span><span class="direction">waves</span></p>"#));
+    }
 }
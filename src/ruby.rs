@@ -0,0 +1,194 @@
+//! Ruby (furigana) annotations for Japanese play scripts.
+//!
+//! Readings of words are written in either of the notations:
+//!
+//! - Aozora Bunko style: `｜漢字《かんじ》` or `漢字《かんじ》`. Without `｜` (or `|`), the run of
+//!   kanji before `《` is annotated.
+//! - Markdown-friendly style: `{漢字|かんじ}`.
+//!
+//! `HtmlRenderer` renders them into `<ruby><rb>漢字</rb><rt>かんじ</rt></ruby>` if
+//! `Options::ruby` is enabled, which is the default for Japanese.
+use pulldown_cmark::Event;
+
+/// A segment of a text with ruby annotations.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Ruby<'a> {
+    Text(&'a str),
+    Ruby { base: &'a str, text: &'a str },
+}
+
+/// Returns `true` if the character is a kanji or an iteration mark of kanji.
+fn is_kanji(c: char) -> bool {
+    matches!(c,
+        '々' | '〆' | 'ヶ'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+/// Matches `{base|text}`, `｜base《text》` or `|base《text》` at the beginning of a string.
+/// Returns the base, the text and the length of the notation.
+fn match_explicit(s: &str) -> Option<(&str, &str, usize)> {
+    if let Some(rest) = s.strip_prefix('{') {
+        let end = rest.find('}')?;
+        let (base, text) = rest[..end].split_once('|')?;
+        if base.is_empty() || text.is_empty() || base.contains('{') {
+            return None;
+        }
+        return Some((base, text, end + 2));
+    }
+
+    let rest = s.strip_prefix('｜').or_else(|| s.strip_prefix('|'))?;
+    let open = rest.find('《')?;
+    let close = rest[open..].find('》')? + open;
+    let base = &rest[..open];
+    let text = &rest[open + '《'.len_utf8()..close];
+    if base.is_empty() || text.is_empty() || base.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((base, text, s.len() - rest.len() + close + '》'.len_utf8()))
+}
+
+/// Splits a text into plain texts and ruby annotations.
+pub fn parse_ruby(s: &str) -> Vec<Ruby<'_>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+
+    while let Some(c) = s[pos..].chars().next() {
+        if let Some((base, text, len)) = match_explicit(&s[pos..]) {
+            if start < pos {
+                segments.push(Ruby::Text(&s[start..pos]));
+            }
+            segments.push(Ruby::Ruby { base, text });
+            pos += len;
+            start = pos;
+            continue;
+        }
+
+        if c == '《' {
+            let base_start = s[start..pos].char_indices()
+                .rev()
+                .take_while(|(_, c)| is_kanji(*c))
+                .last()
+                .map(|(index, _)| start + index);
+            let close = s[pos..].find('》');
+            if let (Some(base_start), Some(close)) = (base_start, close) {
+                let text = &s[pos + c.len_utf8()..pos + close];
+                if !text.is_empty() {
+                    if start < base_start {
+                        segments.push(Ruby::Text(&s[start..base_start]));
+                    }
+                    segments.push(Ruby::Ruby { base: &s[base_start..pos], text });
+                    pos += close + '》'.len_utf8();
+                    start = pos;
+                    continue;
+                }
+            }
+        }
+
+        pos += c.len_utf8();
+    }
+
+    if start < s.len() {
+        segments.push(Ruby::Text(&s[start..]));
+    }
+
+    segments
+}
+
+/// Removes ruby annotations and returns the base text.
+pub fn strip_ruby(s: &str) -> String {
+    parse_ruby(s).into_iter()
+        .map(|segment| match segment {
+            Ruby::Text(text) => text,
+            Ruby::Ruby { base, .. } => base,
+        })
+        .collect()
+}
+
+/// Converts ruby annotations in text events into HTML `<ruby>` elements.
+pub fn expand_ruby<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut expanded = Vec::new();
+
+    for event in events.into_iter() {
+        let s = match event {
+            Event::Text(s) => s,
+            event => {
+                expanded.push(event);
+                continue;
+            },
+        };
+
+        let segments = parse_ruby(&s);
+        if !segments.iter().any(|segment| matches!(segment, Ruby::Ruby { .. })) {
+            expanded.push(Event::Text(s));
+            continue;
+        }
+
+        for segment in segments.into_iter() {
+            match segment {
+                Ruby::Text(text) => {
+                    expanded.push(Event::Text(text.to_owned().into()));
+                },
+                Ruby::Ruby { base, text } => {
+                    expanded.push(Event::Html("<ruby><rb>".into()));
+                    expanded.push(Event::Text(base.to_owned().into()));
+                    expanded.push(Event::Html("</rb><rt>".into()));
+                    expanded.push(Event::Text(text.to_owned().into()));
+                    expanded.push(Event::Html("</rt></ruby>".into()));
+                },
+            }
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_aozora_ruby() {
+        assert_eq!(parse_ruby("湯島｜境内《けいだい》だ"), vec![
+            Ruby::Text("湯島"),
+            Ruby::Ruby { base: "境内", text: "けいだい" },
+            Ruby::Text("だ"),
+        ]);
+        assert_eq!(parse_ruby("お蔦《つた》さん"), vec![
+            Ruby::Text("お"),
+            Ruby::Ruby { base: "蔦", text: "つた" },
+            Ruby::Text("さん"),
+        ]);
+        assert_eq!(parse_ruby("早瀬|主税《ちから》"), vec![
+            Ruby::Text("早瀬"),
+            Ruby::Ruby { base: "主税", text: "ちから" },
+        ]);
+    }
+
+    #[test]
+    fn parse_markdown_ruby() {
+        assert_eq!(parse_ruby("{主税|ちから}です"), vec![
+            Ruby::Ruby { base: "主税", text: "ちから" },
+            Ruby::Text("です"),
+        ]);
+    }
+
+    #[test]
+    fn leave_non_ruby_texts() {
+        assert_eq!(parse_ruby("a|b {c} {|d} 《書名》"), vec![Ruby::Text("a|b {c} {|d} 《書名》")]);
+        assert_eq!(strip_ruby("{主税|ちから}と蔦《つた》"), "主税と蔦");
+    }
+
+    #[test]
+    fn expand_ruby_into_html() {
+        let events = expand_ruby(vec![Event::Text("お蔦《つた》".into())]);
+        let mut buf = String::new();
+        pulldown_cmark::html::push_html(&mut buf, events.into_iter());
+        assert_eq!(buf, "お<ruby><rb>蔦</rb><rt>つた</rt></ruby>");
+    }
+}
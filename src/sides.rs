@@ -0,0 +1,334 @@
+//! Rehearsal sides, or cue scripts, for actors.
+//!
+//! `Sides::extract` makes a `model::Play` which only contains the speeches of the given
+//! characters and the cues before them. A cue is the last words of the preceding speech of
+//! another character. Acts and scenes without the speeches of the characters are removed and
+//! the headings of the others are kept.
+//!
+//! `Sides::to_markdown` writes the sides as a play script and `Sides::push_html` renders them
+//! into HTML.
+use pulldown_cmark::Parser;
+use crate::interface::{MdPlayScriptBuilder, Options};
+use crate::model::{Play, Act, Scene, Block, Speech, Inline, plain_text};
+use crate::stats::{dialogue_lines, tokens};
+
+/// A generator of sides for one or more characters played by an actor.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Sides {
+    characters: Vec<String>,
+    cue_words: usize,
+}
+
+impl Sides {
+    pub fn new(characters: Vec<String>) -> Self {
+        Self {
+            characters,
+            cue_words: 5,
+        }
+    }
+
+    /// Sets the number of words of cues, which is 5 by default.
+    /// Each CJK character is counted as a word.
+    pub fn cue_words(self, n: usize) -> Self {
+        Self {
+            cue_words: n,
+            ..self
+        }
+    }
+
+    fn is_played(&self, character: &str) -> bool {
+        self.characters.iter().any(|name| name == character)
+    }
+
+    /// Makes the sides of a play.
+    pub fn extract(&self, play: &Play) -> Play {
+        let mut sides = Play {
+            title: play.title.clone(),
+            subtitle: play.subtitle.clone(),
+            authors: play.authors.clone(),
+            characters: play.characters.iter()
+                .filter(|character| self.is_played(character))
+                .cloned()
+                .collect(),
+            acts: Vec::new(),
+        };
+
+        for act in play.acts.iter() {
+            let scenes: Vec<Scene> = act.scenes.iter()
+                .map(|scene| self.extract_scene(scene))
+                .filter(|scene| !scene.blocks.is_empty())
+                .collect();
+            if !scenes.is_empty() {
+                sides.acts.push(Act {
+                    title: act.title.clone(),
+                    scenes,
+                });
+            }
+        }
+
+        sides
+    }
+
+    fn extract_scene(&self, scene: &Scene) -> Scene {
+        let mut blocks = Vec::new();
+        let mut previous: Option<&Speech> = None;
+
+        for block in scene.blocks.iter() {
+            let speech = match block {
+                Block::Speech(speech) => speech,
+                _ => continue,
+            };
+
+            if self.is_played(&speech.character) {
+                if let Some(cue) = previous.and_then(|previous| self.make_cue(previous)) {
+                    blocks.push(Block::Speech(cue));
+                }
+                blocks.push(block.clone());
+            }
+
+            previous = Some(speech);
+        }
+
+        Scene {
+            title: scene.title.clone(),
+            blocks,
+        }
+    }
+
+    /// Makes the cue from the preceding speech, which is `None` if the speech is played by
+    /// the same actor or has no words.
+    fn make_cue(&self, speech: &Speech) -> Option<Speech> {
+        if self.is_played(&speech.character) {
+            return None;
+        }
+
+        let dialogue = dialogue_lines(&speech.body).join(" ");
+        let words = last_words(&dialogue, self.cue_words);
+        if words.is_empty() {
+            return None;
+        }
+
+        let text = if words.len() < dialogue.len() {
+            format!("…{}", words)
+        } else {
+            words.to_owned()
+        };
+
+        Some(Speech {
+            character: speech.character.clone(),
+            direction: Vec::new(),
+            body: vec![Inline::Text(text)],
+        })
+    }
+
+    /// Writes the sides of a play as a play script.
+    pub fn to_markdown(&self, play: &Play) -> String {
+        let mut s = String::new();
+        push_markdown(&mut s, &self.extract(play));
+        s
+    }
+
+    /// Renders the sides of a play into HTML.
+    pub fn push_html(&self, s: &mut String, play: &Play, options: Options) {
+        let markdown = self.to_markdown(play);
+        let mdplay = MdPlayScriptBuilder::new()
+            .options(options)
+            .build(Parser::new(&markdown));
+        pulldown_cmark::html::push_html(s, mdplay);
+    }
+}
+
+/// Returns the suffix of a text which consists of the last `n` words.
+///
+/// Words are counted in the same way as `stats::TextCount`.
+fn last_words(s: &str, n: usize) -> &str {
+    let tokens = tokens(s);
+
+    if n == 0 || tokens.is_empty() {
+        return "";
+    }
+
+    let start = tokens[tokens.len().saturating_sub(n)].start();
+    s[start..].trim_end()
+}
+
+/// Escapes a text so that it is read as the same text in a speech.
+///
+/// At the beginning of a line, the characters which begin headings, lists and block quotes
+/// are also escaped.
+fn escape_markdown(s: &str, at_line_head: bool) -> String {
+    let mut escaped = String::new();
+    let indent = s.len() - s.trim_start().len();
+    let digits = s[indent..].len() - s[indent..].trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    for (index, c) in s.char_indices() {
+        let block_start = at_line_head && match index.checked_sub(indent) {
+            Some(0) => matches!(c, '#' | '-' | '+' | '=' | '>'),
+            Some(pos) => digits > 0 && pos == digits && matches!(c, '.' | ')'),
+            None => false,
+        };
+        if block_start || matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '(' | ')') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn push_inlines_markdown(s: &mut String, inlines: &[Inline]) {
+    for inline in inlines.iter() {
+        match inline {
+            Inline::Text(text) => {
+                let at_line_head = s.ends_with('\n');
+                *s += &escape_markdown(text, at_line_head);
+            },
+            Inline::Code(code) => *s += &format!("`{}`", code),
+            Inline::Html(html) => *s += html,
+            Inline::SoftBreak => s.push('\n'),
+            Inline::HardBreak => *s += "\\\n",
+            Inline::Emphasis(v) => push_wrapped_markdown(s, "*", v),
+            Inline::Strong(v) => push_wrapped_markdown(s, "**", v),
+            Inline::Strikethrough(v) => push_wrapped_markdown(s, "~~", v),
+            Inline::Link { url, content, .. } => {
+                s.push('[');
+                push_inlines_markdown(s, content);
+                *s += &format!("]({})", url);
+            },
+            Inline::Image { url, content, .. } => {
+                s.push_str("![");
+                push_inlines_markdown(s, content);
+                *s += &format!("]({})", url);
+            },
+            Inline::Direction(v) => {
+                s.push('(');
+                push_inlines_markdown(s, v);
+                s.push(')');
+            },
+        }
+    }
+}
+
+fn push_wrapped_markdown(s: &mut String, delim: &str, inlines: &[Inline]) {
+    s.push_str(delim);
+    push_inlines_markdown(s, inlines);
+    s.push_str(delim);
+}
+
+fn push_speech_markdown(s: &mut String, speech: &Speech) {
+    s.push_str(&speech.character);
+    if !speech.direction.is_empty() {
+        *s += &format!(" ({})", plain_text(&speech.direction));
+    }
+    s.push('>');
+    if !speech.body.is_empty() {
+        s.push(' ');
+        push_inlines_markdown(s, &speech.body);
+    }
+    s.push('\n');
+}
+
+/// Writes a play as a play script. Only headings and speeches are written.
+fn push_markdown(s: &mut String, play: &Play) {
+    for act in play.acts.iter() {
+        if let Some(title) = act.title.as_ref() {
+            *s += &format!("## {}\n\n", title);
+        }
+
+        for scene in act.scenes.iter() {
+            if let Some(title) = scene.title.as_ref() {
+                *s += &format!("### {}\n\n", title);
+            }
+
+            for block in scene.blocks.iter() {
+                if let Block::Speech(speech) = block {
+                    push_speech_markdown(s, speech);
+                    s.push('\n');
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::interface::Params;
+
+    fn parse(s: &str) -> Play {
+        Play::from_events(Parser::new(s), &Options::default(), Params::default())
+    }
+
+    #[test]
+    fn take_last_words() {
+        assert_eq!(last_words("Dix-neuf pieds sur vingt-six.", 2), "sur vingt-six.");
+        assert_eq!(last_words("Quoi ?", 5), "Quoi ?");
+        assert_eq!(last_words("Quoi ?", 1), "Quoi ?");
+        assert_eq!(last_words("お前は何を", 2), "何を");
+        assert_eq!(last_words("", 3), "");
+    }
+
+    #[test]
+    fn make_sides_with_cues() {
+        let play = parse(r#"## Acte I
+
+### Scène I
+
+Figaro> Dix-neuf pieds sur vingt-six.
+Suzanne> Tiens, Figaro, voilà mon petit chapeau. (montrant)
+Figaro> Sans comparaison.
+
+### Scène II
+
+Suzanne> Non.
+Le Comte> Non plus."#);
+        let sides = Sides::new(vec!["Figaro".to_owned()]).cue_words(3);
+        assert_eq!(sides.to_markdown(&play), r#"## Acte I
+
+### Scène I
+
+Figaro> Dix-neuf pieds sur vingt-six.
+
+Suzanne> …mon petit chapeau.
+
+Figaro> Sans comparaison.
+
+"#);
+    }
+
+    #[test]
+    fn escape_markdown_in_speeches() {
+        let play = parse("A> a (b) c \\> d\n\\# e\n\\- f\n1\\. g\nB\\> h");
+        let body = |play: &Play| play.speeches().map(|speech| plain_text(&speech.body)).collect::<Vec<_>>();
+        assert_eq!(body(&play), vec!["a b c > d # e - f 1. g B> h".to_owned()]);
+
+        let markdown = Sides::new(vec!["A".to_owned()]).to_markdown(&play);
+        assert_eq!(markdown, "A> a (b) c \\> d\n\\# e\n\\- f\n1\\. g\nB\\> h\n\n");
+        assert_eq!(body(&parse(&markdown)), body(&play));
+    }
+
+    #[test]
+    fn make_sides_for_doubled_roles() {
+        let play = parse("A> One.\nB> Two.\nC> Three four.\nB> Five.");
+        let sides = Sides::new(vec!["A".to_owned(), "B".to_owned()]).cue_words(1);
+        let speeches: Vec<_> = sides.extract(&play).speeches()
+            .map(|speech| (speech.character.clone(), plain_text(&speech.body)))
+            .collect();
+        assert_eq!(speeches, vec![
+            ("A".to_owned(), "One.".to_owned()),
+            ("B".to_owned(), "Two.".to_owned()),
+            ("C".to_owned(), "…four.".to_owned()),
+            ("B".to_owned(), "Five.".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn render_sides_to_html() {
+        let play = parse("A> Hello!\nB> (waving) Bye.");
+        let mut buf = String::new();
+        Sides::new(vec!["B".to_owned()]).push_html(&mut buf, &play, Options::default());
+        assert!(buf.contains(r#"<span class="character">A</span>"#));
+        assert!(buf.contains(r#"<span class="direction">waving</span>"#));
+    }
+}
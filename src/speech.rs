@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 use pulldown_cmark::{Event, CowStr};
-use crate::{find_one_of, find_puncts_end};
-use crate::parser::split_speech_heading;
+use crate::find_puncts_end;
+use crate::parser::{Delimiters, split_speech_heading_with};
 
 #[derive(Debug,Clone,PartialEq)]
 pub struct Speech<'a> {
@@ -34,14 +34,23 @@ impl<'a> Direction<'a> {
     }
 }
 
+/// Returns `true` if a bracket of directions is kept in output with the delimiters.
+fn is_preserved(c: char, delimiters: &Delimiters) -> bool {
+    delimiters.preserve_brackets && !c.is_ascii()
+}
+
 pub fn parse_speech<'a>(events: Vec<Event<'a>>) -> Result<Speech<'a>, Vec<Event<'a>>> {
+    parse_speech_with(events, &Delimiters::default())
+}
+
+pub fn parse_speech_with<'a>(events: Vec<Event<'a>>, delimiters: &Delimiters) -> Result<Speech<'a>, Vec<Event<'a>>> {
     let (heading, first) = match events.first() {
         Some(Event::Text(s)) => {
             let s = s.to_string();
-            if let Some((heading, line)) = split_speech_heading(s.as_ref()) {
+            if let Some((heading, line)) = split_speech_heading_with(s.as_ref(), delimiters) {
                 let heading = heading.to_owned();
                 let line = line.to_owned();
-                (parse_heading(&heading), Event::Text(line.into()))
+                (parse_heading_with(&heading, delimiters), Event::Text(line.into()))
             } else {
                 return Err(events);
             }
@@ -55,7 +64,7 @@ pub fn parse_speech<'a>(events: Vec<Event<'a>>) -> Result<Speech<'a>, Vec<Event<
     }
     remove_trailing_softbreak(&mut speech);
 
-    let body = parse_body(speech);
+    let body = parse_body_with(speech, delimiters);
 
     Ok(Speech {
         heading: heading,
@@ -64,8 +73,12 @@ pub fn parse_speech<'a>(events: Vec<Event<'a>>) -> Result<Speech<'a>, Vec<Event<
 }
 
 pub fn parse_heading(s: &str) -> Heading<'static> {
-    let open_paren = match s.find('(') {
-        Some(pos) => pos,
+    parse_heading_with(s, &Delimiters::default())
+}
+
+pub fn parse_heading_with(s: &str, delimiters: &Delimiters) -> Heading<'static> {
+    let (open_paren, open) = match s.char_indices().find(|(_, c)| delimiters.is_open(*c)) {
+        Some(found) => found,
         None => {
             let character = s.trim().to_owned();
             return Heading {
@@ -76,18 +89,21 @@ pub fn parse_heading(s: &str) -> Heading<'static> {
     };
 
     let character = s[..open_paren].trim().to_owned();
-    let s = &s[open_paren+1..];
-    let mut close_paren = s.len();
-    for (index, c) in s.char_indices() {
-        if c == ')' {
-            close_paren = index;
-            break;
-        }
-    }
+    let s = &s[open_paren+open.len_utf8()..];
+    // Only the bracket which corresponds to the open one closes the direction.
+    let close = delimiters.close_of(open)
+        .and_then(|close| s.find(close).map(|index| (index, close)));
+    let close_paren = close.map_or(s.len(), |(index, _)| index);
+    let close = close.map(|(_, close)| close);
 
-    let s = s[..close_paren].to_owned();
     let mut direction = Direction::new();
-    direction.push_string(s);
+    if is_preserved(open, delimiters) {
+        direction.push_string(open.to_string());
+    }
+    direction.push_string(s[..close_paren].to_owned());
+    if let Some(close) = close.filter(|c| is_preserved(*c, delimiters)) {
+        direction.push_string(close.to_string());
+    }
 
     Heading {
         character: character.into(),
@@ -96,25 +112,41 @@ pub fn parse_heading(s: &str) -> Heading<'static> {
 }
 
 pub fn parse_body<'a>(events: Vec<Event<'a>>) -> Vec<Inline<'a>> {
+    parse_body_with(events, &Delimiters::default())
+}
+
+/// Returns the bracket if a text is a single bracket which satisfies a predicate.
+fn single_bracket(s: &str, pred: impl Fn(char) -> bool) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if pred(c) => Some(c),
+        _ => None,
+    }
+}
+
+pub fn parse_body_with<'a>(events: Vec<Event<'a>>, delimiters: &Delimiters) -> Vec<Inline<'a>> {
     let mut body = Vec::new();
     let mut direction = Vec::new();
     let mut paren_level = 0usize;
 
-    for event in ParenSplitter::new(events.into_iter()) {
+    for event in ParenSplitter::with_delimiters(events.into_iter(), *delimiters) {
         match event {
-            Event::Text(s) if s.as_ref() == "(" => {
-                if paren_level > 0 {
+            Event::Text(s) if single_bracket(&s, |c| delimiters.is_open(c)).is_some() => {
+                if paren_level > 0 || single_bracket(&s, |c| is_preserved(c, delimiters)).is_some() {
                     direction.push(Event::Text(s));
                 }
 
                 paren_level = paren_level + 1;
             },
-            Event::Text(s) if s.as_ref() == ")" => {
+            Event::Text(s) if single_bracket(&s, |c| delimiters.is_close(c)).is_some() => {
                 match paren_level {
                     0 => {
                         body.push(Inline::Event(Event::Text(s)));
                     },
                     1 => {
+                        if single_bracket(&s, |c| is_preserved(c, delimiters)).is_some() {
+                            direction.push(Event::Text(s));
+                        }
                         let mut pushed = Vec::new();
                         std::mem::swap(&mut pushed, &mut direction);
                         let pushed = Direction(pushed);
@@ -149,6 +181,7 @@ pub fn parse_body<'a>(events: Vec<Event<'a>>) -> Vec<Inline<'a>> {
 pub struct ParenSplitter<'a, I> {
     iter: I,
     queue: VecDeque<Event<'a>>,
+    delimiters: Delimiters,
 }
 
 impl<'a, I> ParenSplitter<'a, I>
@@ -156,9 +189,14 @@ where
     I: Iterator<Item=Event<'a>>,
 {
     pub fn new(iter: I) -> Self {
+        Self::with_delimiters(iter, Delimiters::default())
+    }
+
+    pub fn with_delimiters(iter: I, delimiters: Delimiters) -> Self {
         Self {
             iter: iter,
             queue: VecDeque::new(),
+            delimiters: delimiters,
         }
     }
 }
@@ -176,7 +214,7 @@ where
 
         match self.iter.next() {
             Some(Event::Text(s)) => {
-                for text in split_at_paren(s).into_iter() {
+                for text in split_at_paren(s, &self.delimiters).into_iter() {
                     self.queue.push_back(Event::Text(text.into()));
                 }
             },
@@ -187,7 +225,7 @@ where
     }
 }
 
-fn split_at_paren<T: AsRef<str>>(s: T) -> Vec<String> {
+fn split_at_paren<T: AsRef<str>>(s: T, delimiters: &Delimiters) -> Vec<String> {
     let mut s = s.as_ref();
     let mut v = Vec::new();
 
@@ -196,7 +234,7 @@ fn split_at_paren<T: AsRef<str>>(s: T) -> Vec<String> {
             break;
         }
 
-        match find_one_of(s, "()") {
+        match s.char_indices().find(|(_, c)| delimiters.is_open(*c) || delimiters.is_close(*c)) {
             Some((index, c)) => {
                 let before = &s[..index];
                 let (parens, after) = find_puncts_end(&s[index..], c);
@@ -274,11 +312,29 @@ mod test {
         });
     }
 
+    #[test]
+    fn parse_heading_with_mismatched_brackets() {
+        let delimiters = Delimiters { tortoise_shell: true, ..Delimiters::japanese() };
+        assert_eq!(parse_heading_with("お蔦（泣いて）", &delimiters).direction, Direction(vec![Event::Text("泣いて".into())]));
+        assert_eq!(parse_heading_with("お蔦（泣いて)", &delimiters).direction, Direction(vec![Event::Text("泣いて)".into())]));
+        assert_eq!(parse_heading_with("お蔦〔泣いて）", &delimiters).direction, Direction(vec![Event::Text("泣いて）".into())]));
+        assert_eq!(parse_heading_with("A (running）) ", &delimiters).direction, Direction(vec![Event::Text("running）".into())]));
+    }
+
     #[test]
     fn split_parens_in_direction() {
-        assert_eq!(split_at_paren("A (running)"), vec![S("A "), S("("), S("running"), S(")")]);
-        assert_eq!(split_at_paren("xx (dd) yy"), vec![S("xx "), S("("), S("dd"), S(")"), S(" yy")]);
-        assert_eq!(split_at_paren("Escaped (( example"), vec![S("Escaped "), S("(("), S(" example")]);
+        let delimiters = Delimiters::default();
+        assert_eq!(split_at_paren("A (running)", &delimiters), vec![S("A "), S("("), S("running"), S(")")]);
+        assert_eq!(split_at_paren("xx (dd) yy", &delimiters), vec![S("xx "), S("("), S("dd"), S(")"), S(" yy")]);
+        assert_eq!(split_at_paren("Escaped (( example", &delimiters), vec![S("Escaped "), S("(("), S(" example")]);
+        assert_eq!(split_at_paren("ああ（泣く）", &delimiters), vec![S("ああ（泣く）")]);
+    }
+
+    #[test]
+    fn split_full_width_parens_in_direction() {
+        let delimiters = Delimiters { tortoise_shell: true, ..Delimiters::japanese() };
+        assert_eq!(split_at_paren("ああ（泣く）", &delimiters), vec![S("ああ"), S("（"), S("泣く"), S("）")]);
+        assert_eq!(split_at_paren("〔間〕", &delimiters), vec![S(""), S("〔"), S("間"), S("〕")]);
     }
 
     #[test]
@@ -355,6 +411,48 @@ mod test {
         assert_eq!(parse_speech(input), Ok(output));
     }
 
+    #[test]
+    fn parse_japanese_speech_with_full_width_delimiters() {
+        let input = vec![
+            Event::Text("お蔦（振返って）＞ 貴方。〔間〕".into()),
+        ];
+        let output = Speech {
+            heading: Heading {
+                character: "お蔦".into(),
+                direction: Direction(vec![Event::Text("振返って".into())]),
+            },
+            body: vec![
+                Inline::Event(Event::Text("貴方。".into())),
+                Inline::Direction(Direction(vec![
+                        Event::Text("間".into()),
+                ])),
+            ],
+        };
+        let delimiters = Delimiters { tortoise_shell: true, ..Delimiters::japanese() };
+        assert_eq!(parse_speech_with(input, &delimiters), Ok(output));
+    }
+
+    #[test]
+    fn parse_body_preserving_brackets() {
+        let input = vec![
+            Event::Text("貴方。（泣く） (exit)".into()),
+        ];
+        let output = vec![
+            Inline::Event(Event::Text("貴方。".into())),
+            Inline::Direction(Direction(vec![
+                    Event::Text("（".into()),
+                    Event::Text("泣く".into()),
+                    Event::Text("）".into()),
+            ])),
+            Inline::Event(Event::Text(" ".into())),
+            Inline::Direction(Direction(vec![
+                    Event::Text("exit".into()),
+            ])),
+        ];
+        let delimiters = Delimiters { preserve_brackets: true, ..Delimiters::japanese() };
+        assert_eq!(parse_body_with(input, &delimiters), output);
+    }
+
     #[test]
     fn trim_start_of_body_line_head() {
         let input = vec![
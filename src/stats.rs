@@ -0,0 +1,541 @@
+//! Statistics of play scripts.
+//!
+//! `Stats::from_play` counts the speeches, lines and words of each character in a
+//! `model::Play`. Directions are not counted as dialogue.
+//! Words are counted for Latin scripts, and characters of CJK scripts are counted separately
+//! since they are not separated by spaces.
+//!
+//! `RunningTime::estimate` estimates the running time of each scene from the lengths of the
+//! dialogue and the directions with a `SpeakingRate`.
+use std::ops::{Add, AddAssign};
+use serde::Serialize;
+use crate::model::{Play, Scene, Block, Inline, plain_text};
+
+/// Returns `true` if the character is a kana, an ideograph or a hangul syllable.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3005}'..='\u{3007}'
+        | '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff66}'..='\u{ff9f}'
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || ('\u{3000}'..='\u{303f}').contains(&c) || ('\u{ff01}'..='\u{ff0f}').contains(&c)
+}
+
+/// A word or a CJK character counted by `TextCount`, with the byte offset where it begins.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub(crate) enum Token {
+    Word(usize),
+    Cjk(usize),
+}
+
+impl Token {
+    pub(crate) fn start(&self) -> usize {
+        match self {
+            Token::Word(start) | Token::Cjk(start) => *start,
+        }
+    }
+}
+
+/// Splits a text into the words and the CJK characters which `TextCount` counts.
+pub(crate) fn tokens(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut run_start = None;
+    let mut in_word = false;
+
+    for (index, c) in s.char_indices() {
+        if is_cjk(c) {
+            tokens.push(Token::Cjk(index));
+            run_start = None;
+            in_word = false;
+        } else if is_separator(c) {
+            run_start = None;
+            in_word = false;
+        } else {
+            let start = *run_start.get_or_insert(index);
+            if c.is_alphanumeric() && !in_word {
+                tokens.push(Token::Word(start));
+                in_word = true;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// The numbers of words and CJK characters in a text.
+#[derive(Debug,Clone,Copy,Default,PartialEq,Serialize)]
+pub struct TextCount {
+    pub words: usize,
+    pub cjk_characters: usize,
+}
+
+impl TextCount {
+    /// Counts a text. A word is a run of characters which are neither separators nor CJK
+    /// characters and contains a letter or a digit.
+    pub fn of(s: &str) -> Self {
+        let mut count = TextCount::default();
+
+        for token in tokens(s).iter() {
+            match token {
+                Token::Word(_) => count.words += 1,
+                Token::Cjk(_) => count.cjk_characters += 1,
+            }
+        }
+
+        count
+    }
+
+    /// Returns the sum of words and CJK characters, which is used to compare lengths of
+    /// texts in mixed scripts.
+    pub fn len(&self) -> usize {
+        self.words + self.cjk_characters
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Add for TextCount {
+    type Output = TextCount;
+
+    fn add(self, other: TextCount) -> TextCount {
+        TextCount {
+            words: self.words + other.words,
+            cjk_characters: self.cjk_characters + other.cjk_characters,
+        }
+    }
+}
+
+impl AddAssign for TextCount {
+    fn add_assign(&mut self, other: TextCount) {
+        *self = *self + other;
+    }
+}
+
+fn push_dialogue(lines: &mut Vec<String>, inlines: &[Inline]) {
+    for inline in inlines.iter() {
+        match inline {
+            Inline::Text(s) | Inline::Code(s) => lines.last_mut().unwrap().push_str(s),
+            Inline::SoftBreak | Inline::HardBreak => lines.push(String::new()),
+            Inline::Html(_) | Inline::Direction(_) => {},
+            Inline::Emphasis(v) | Inline::Strong(v) | Inline::Strikethrough(v) => push_dialogue(lines, v),
+            Inline::Link { content, .. } | Inline::Image { content, .. } => push_dialogue(lines, content),
+        }
+    }
+}
+
+/// Returns the lines of a speech body without directions.
+/// Lines which have no words are removed.
+pub fn dialogue_lines(inlines: &[Inline]) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    push_dialogue(&mut lines, inlines);
+    lines.retain(|line| !TextCount::of(line).is_empty());
+    lines
+}
+
+/// Counts the directions in inline elements.
+fn direction_count(inlines: &[Inline]) -> TextCount {
+    let mut count = TextCount::default();
+
+    for inline in inlines.iter() {
+        match inline {
+            Inline::Direction(v) => count += TextCount::of(&plain_text(v)),
+            Inline::Emphasis(v) | Inline::Strong(v) | Inline::Strikethrough(v) => count += direction_count(v),
+            Inline::Link { content, .. } | Inline::Image { content, .. } => count += direction_count(content),
+            _ => {},
+        }
+    }
+
+    count
+}
+
+fn dialogue_count(inlines: &[Inline]) -> TextCount {
+    dialogue_lines(inlines).iter()
+        .fold(TextCount::default(), |count, line| count + TextCount::of(line))
+}
+
+/// Rates of speaking to estimate running time.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct SpeakingRate {
+    words_per_minute: f64,
+    cjk_characters_per_minute: f64,
+    direction_weight: f64,
+}
+
+impl Default for SpeakingRate {
+    fn default() -> Self {
+        Self {
+            words_per_minute: 150.0,
+            cjk_characters_per_minute: 300.0,
+            direction_weight: 0.5,
+        }
+    }
+}
+
+impl SpeakingRate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the rate for Latin scripts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rate is not positive or not finite.
+    pub fn words_per_minute(self, val: f64) -> Self {
+        assert!(is_valid_rate(val), "words per minute must be positive and finite: {}", val);
+        Self {
+            words_per_minute: val,
+            ..self
+        }
+    }
+
+    /// Sets the rate for Japanese and other CJK scripts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rate is not positive or not finite.
+    pub fn cjk_characters_per_minute(self, val: f64) -> Self {
+        assert!(is_valid_rate(val), "characters per minute must be positive and finite: {}", val);
+        Self {
+            cjk_characters_per_minute: val,
+            ..self
+        }
+    }
+
+    /// Sets the ratio of the time for directions to the time to read them aloud.
+    pub fn direction_weight(self, val: f64) -> Self {
+        Self {
+            direction_weight: val,
+            ..self
+        }
+    }
+
+    /// Returns the minutes to speak a text.
+    pub fn minutes(&self, count: TextCount) -> f64 {
+        count.words as f64 / self.words_per_minute
+            + count.cjk_characters as f64 / self.cjk_characters_per_minute
+    }
+}
+
+/// Returns `true` if the speaking rate is positive and finite. NaN is not a valid rate.
+pub fn is_valid_rate(val: f64) -> bool {
+    val > 0.0 && val.is_finite()
+}
+
+/// Formats minutes in the form `12 min`.
+pub fn format_minutes(minutes: f64) -> String {
+    if minutes < 1.0 {
+        "< 1 min".to_owned()
+    } else {
+        format!("{:.0} min", minutes)
+    }
+}
+
+/// The estimated running time of a scene.
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct SceneTime {
+    pub act: Option<String>,
+    pub scene: Option<String>,
+    pub dialogue: TextCount,
+    pub directions: TextCount,
+    pub minutes: f64,
+}
+
+impl SceneTime {
+    /// Estimates the running time of a scene.
+    ///
+    /// Speeches and monologues are counted as dialogue. Directions and prose paragraphs are
+    /// counted as directions.
+    pub fn estimate(scene: &Scene, rate: &SpeakingRate) -> Self {
+        let mut dialogue = TextCount::default();
+        let mut directions = TextCount::default();
+
+        for block in scene.blocks.iter() {
+            match block {
+                Block::Speech(speech) => {
+                    dialogue += dialogue_count(&speech.body);
+                    directions += TextCount::of(&plain_text(&speech.direction));
+                    directions += direction_count(&speech.body);
+                },
+                Block::Monologue { content } => {
+                    dialogue += dialogue_count(content);
+                    directions += direction_count(content);
+                },
+                Block::Direction { content } | Block::Prose { content } => {
+                    directions += TextCount::of(&plain_text(content));
+                },
+                Block::Heading { .. } => {},
+            }
+        }
+
+        Self {
+            act: None,
+            scene: scene.title.clone(),
+            dialogue,
+            directions,
+            minutes: rate.minutes(dialogue) + rate.minutes(directions) * rate.direction_weight,
+        }
+    }
+}
+
+/// The estimated running time of a play.
+#[derive(Debug,Clone,Default,PartialEq,Serialize)]
+pub struct RunningTime {
+    pub scenes: Vec<SceneTime>,
+    pub minutes: f64,
+}
+
+impl RunningTime {
+    pub fn estimate(play: &Play, rate: &SpeakingRate) -> Self {
+        let mut time = RunningTime::default();
+
+        for act in play.acts.iter() {
+            for scene in act.scenes.iter() {
+                let scene = SceneTime {
+                    act: act.title.clone(),
+                    ..SceneTime::estimate(scene, rate)
+                };
+                time.minutes += scene.minutes;
+                time.scenes.push(scene);
+            }
+        }
+
+        time
+    }
+}
+
+/// The longest speech of a character.
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct LongestSpeech {
+    /// The index of the speech in the play.
+    pub index: usize,
+    pub words: usize,
+    pub cjk_characters: usize,
+    /// The first line of the speech.
+    pub opening: String,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct CharacterStats {
+    pub character: String,
+    pub speeches: usize,
+    pub lines: usize,
+    pub words: usize,
+    pub cjk_characters: usize,
+    /// The ratio of the words and CJK characters of the character to those of all characters.
+    pub share: f64,
+    pub longest_speech: Option<LongestSpeech>,
+}
+
+impl CharacterStats {
+    fn new(character: &str) -> Self {
+        Self {
+            character: character.to_owned(),
+            speeches: 0,
+            lines: 0,
+            words: 0,
+            cjk_characters: 0,
+            share: 0.0,
+            longest_speech: None,
+        }
+    }
+
+    pub fn count(&self) -> TextCount {
+        TextCount {
+            words: self.words,
+            cjk_characters: self.cjk_characters,
+        }
+    }
+}
+
+/// Statistics of the dialogue of a play.
+#[derive(Debug,Clone,Default,PartialEq,Serialize)]
+pub struct Stats {
+    pub speeches: usize,
+    pub lines: usize,
+    pub words: usize,
+    pub cjk_characters: usize,
+    /// Characters in order of first appearance.
+    pub characters: Vec<CharacterStats>,
+    pub running_time: RunningTime,
+}
+
+impl Stats {
+    pub fn from_play(play: &Play) -> Self {
+        Self::from_play_with_rate(play, &SpeakingRate::default())
+    }
+
+    /// Counts a play and estimates its running time with the given rate.
+    pub fn from_play_with_rate(play: &Play, rate: &SpeakingRate) -> Self {
+        let mut stats = Stats {
+            running_time: RunningTime::estimate(play, rate),
+            ..Default::default()
+        };
+
+        for (index, speech) in play.speeches().enumerate() {
+            let lines = dialogue_lines(&speech.body);
+            let count = dialogue_count(&speech.body);
+
+            let position = stats.characters.iter()
+                .position(|c| c.character == speech.character);
+            let character = match position {
+                Some(position) => &mut stats.characters[position],
+                None => {
+                    stats.characters.push(CharacterStats::new(&speech.character));
+                    stats.characters.last_mut().unwrap()
+                },
+            };
+
+            character.speeches += 1;
+            character.lines += lines.len();
+            character.words += count.words;
+            character.cjk_characters += count.cjk_characters;
+
+            let is_longer = match character.longest_speech.as_ref() {
+                Some(longest) => longest.words + longest.cjk_characters < count.len(),
+                None => true,
+            };
+            if is_longer {
+                character.longest_speech = Some(LongestSpeech {
+                    index,
+                    words: count.words,
+                    cjk_characters: count.cjk_characters,
+                    opening: lines.first().map(|line| line.trim().to_owned()).unwrap_or_default(),
+                });
+            }
+
+            stats.speeches += 1;
+            stats.lines += lines.len();
+            stats.words += count.words;
+            stats.cjk_characters += count.cjk_characters;
+        }
+
+        let total = stats.count().len();
+        if total > 0 {
+            for character in stats.characters.iter_mut() {
+                character.share = character.count().len() as f64 / total as f64;
+            }
+        }
+
+        stats
+    }
+
+    pub fn count(&self) -> TextCount {
+        TextCount {
+            words: self.words,
+            cjk_characters: self.cjk_characters,
+        }
+    }
+
+    pub fn get(&self, character: &str) -> Option<&CharacterStats> {
+        self.characters.iter().find(|c| c.character == character)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use crate::{Options, Params};
+
+    fn stats(s: &str) -> Stats {
+        Stats::from_play(&Play::from_events(Parser::new(s), &Options::default(), Params::default()))
+    }
+
+    #[test]
+    fn count_words_and_cjk_characters() {
+        assert_eq!(TextCount::of("Dix-neuf pieds sur vingt-six. — Oh !"), TextCount { words: 5, cjk_characters: 0 });
+        assert_eq!(TextCount::of("貴方……貴方。"), TextCount { words: 0, cjk_characters: 4 });
+        assert_eq!(TextCount::of("Tokyoに行く、Osaka"), TextCount { words: 2, cjk_characters: 3 });
+    }
+
+    #[test]
+    fn count_speeches_of_characters() {
+        let s = r#"Figaro> Dix-neuf pieds
+sur vingt-six. (il mesure)
+Suzanne> Qu'est-ce que tu mesures ?
+Figaro> Je regarde.
+
+(Suzanne s'assied.)
+"#;
+        let stats = stats(s);
+        assert_eq!((stats.speeches, stats.lines, stats.words), (3, 4, 10));
+
+        let figaro = stats.get("Figaro").unwrap();
+        assert_eq!((figaro.speeches, figaro.lines, figaro.words), (2, 3, 6));
+        assert_eq!(figaro.share, 0.6);
+        assert_eq!(figaro.longest_speech, Some(LongestSpeech {
+            index: 0,
+            words: 4,
+            cjk_characters: 0,
+            opening: "Dix-neuf pieds".to_owned(),
+        }));
+        assert_eq!(stats.characters[1].character, "Suzanne");
+        assert_eq!(stats.characters[1].words, 4);
+    }
+
+    #[test]
+    fn count_japanese_speeches() {
+        let stats = stats("お蔦> 貴方……貴方。\n早瀬> ああ。(と驚いたように返事する。)");
+        assert_eq!(stats.get("お蔦").unwrap().cjk_characters, 4);
+        assert_eq!(stats.get("早瀬").unwrap().cjk_characters, 2);
+        assert_eq!(stats.cjk_characters, 6);
+    }
+
+    #[test]
+    fn estimate_running_time_of_scenes() {
+        let s = r#"## Act I
+
+### Scene 1
+
+A> One two three four five six. (six words)
+
+(Exit.)
+
+### Scene 2
+
+お蔦> 貴方……貴方。
+"#;
+        let rate = SpeakingRate::new()
+            .words_per_minute(6.0)
+            .cjk_characters_per_minute(2.0)
+            .direction_weight(1.0);
+        let play = Play::from_events(Parser::new(s), &Options::default(), Params::default());
+        let time = Stats::from_play_with_rate(&play, &rate).running_time;
+        assert_eq!(time.scenes.len(), 2);
+        assert_eq!(time.scenes[0].act.as_deref(), Some("Act I"));
+        assert_eq!(time.scenes[0].scene.as_deref(), Some("Scene 1"));
+        assert_eq!(time.scenes[0].dialogue, TextCount { words: 6, cjk_characters: 0 });
+        assert_eq!(time.scenes[0].directions, TextCount { words: 3, cjk_characters: 0 });
+        assert_eq!(time.scenes[0].minutes, 1.5);
+        assert_eq!(time.scenes[1].minutes, 2.0);
+        assert_eq!(time.minutes, 3.5);
+        assert_eq!(format_minutes(time.minutes), "4 min");
+        assert_eq!(format_minutes(0.2), "< 1 min");
+    }
+
+    #[test]
+    fn validate_speaking_rates() {
+        assert!(is_valid_rate(150.0));
+        assert!(!is_valid_rate(0.0));
+        assert!(!is_valid_rate(-1.0));
+        assert!(!is_valid_rate(f64::NAN));
+        assert!(!is_valid_rate(f64::INFINITY));
+    }
+
+    #[test]
+    #[should_panic]
+    fn reject_zero_words_per_minute() {
+        SpeakingRate::new().words_per_minute(0.0);
+    }
+}
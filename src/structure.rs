@@ -0,0 +1,162 @@
+//! Acts and scenes of play scripts.
+//!
+//! Markdown headings begin structural units of a play. By default, level 2 headings begin
+//! acts and level 3 headings begin scenes. `Structure` changes the levels, and recognizes
+//! headings by the names of units such as `Acte`, `Scène` or `幕` regardless of their levels.
+use pulldown_cmark::Event;
+
+/// A structural unit of a play.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Unit {
+    Act,
+    Scene,
+}
+
+/// The mapping from headings to structural units.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Structure {
+    act_level: Option<u32>,
+    scene_level: Option<u32>,
+    act_names: Vec<String>,
+    scene_names: Vec<String>,
+}
+
+impl Default for Structure {
+    fn default() -> Self {
+        Self {
+            act_level: Some(2),
+            scene_level: Some(3),
+            act_names: Vec::new(),
+            scene_names: Vec::new(),
+        }
+    }
+}
+
+impl Structure {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The structure of Japanese plays, which recognizes `幕` and `場` besides the levels.
+    pub fn japanese() -> Self {
+        Self::default()
+            .act_names(vec!["幕".to_owned()])
+            .scene_names(vec!["場".to_owned()])
+    }
+
+    /// Sets the level of headings which begin acts. `None` disables acts by levels.
+    pub fn act_level(self, level: Option<u32>) -> Self {
+        Self {
+            act_level: level,
+            ..self
+        }
+    }
+
+    /// Sets the level of headings which begin scenes. `None` disables scenes by levels.
+    pub fn scene_level(self, level: Option<u32>) -> Self {
+        Self {
+            scene_level: level,
+            ..self
+        }
+    }
+
+    /// Sets the names of acts, e.g. `Act`, `Acte` or `幕`.
+    pub fn act_names(self, names: Vec<String>) -> Self {
+        Self {
+            act_names: names,
+            ..self
+        }
+    }
+
+    /// Sets the names of scenes, e.g. `Scene`, `Scène` or `場`.
+    pub fn scene_names(self, names: Vec<String>) -> Self {
+        Self {
+            scene_names: names,
+            ..self
+        }
+    }
+
+    /// Returns the unit which a heading begins.
+    ///
+    /// A heading whose first word is one of the names, ignoring case, begins the unit.
+    /// A first word such as `第一幕` which starts with `第` and ends with a name also begins it.
+    /// The names take precedence over the levels.
+    pub fn unit(&self, level: u32, title: &str) -> Option<Unit> {
+        if matches_name(&self.act_names, title) {
+            Some(Unit::Act)
+        } else if matches_name(&self.scene_names, title) {
+            Some(Unit::Scene)
+        } else if Some(level) == self.act_level {
+            Some(Unit::Act)
+        } else if Some(level) == self.scene_level {
+            Some(Unit::Scene)
+        } else {
+            None
+        }
+    }
+}
+
+fn matches_name(names: &[String], title: &str) -> bool {
+    let first = match title.split_whitespace().next() {
+        Some(first) => first,
+        None => return false,
+    };
+    let lowercase = first.to_lowercase();
+
+    names.iter().any(|name| {
+        lowercase == name.to_lowercase() || (first.starts_with('第') && first.ends_with(name.as_str()))
+    })
+}
+
+/// Concatenates the texts in the events of a heading.
+pub(crate) fn heading_text<'a, 'b, I>(events: I) -> String
+where
+    'a: 'b,
+    I: Iterator<Item=&'b Event<'a>>,
+{
+    events
+        .filter_map(|event| match event {
+            Event::Text(s) | Event::Code(s) => Some(s.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn map_heading_levels() {
+        let structure = Structure::default();
+        assert_eq!(structure.unit(2, "Acte Premier"), Some(Unit::Act));
+        assert_eq!(structure.unit(3, "Scène I"), Some(Unit::Scene));
+        assert_eq!(structure.unit(4, "Note"), None);
+
+        let structure = structure.act_level(None).scene_level(Some(2));
+        assert_eq!(structure.unit(2, "Scène I"), Some(Unit::Scene));
+        assert_eq!(structure.unit(3, "Scène I"), None);
+    }
+
+    #[test]
+    fn recognize_headings_by_names() {
+        let structure = Structure::new()
+            .act_names(vec!["Acte".to_owned(), "幕".to_owned()])
+            .scene_names(vec!["Scène".to_owned(), "場".to_owned()]);
+        assert_eq!(structure.unit(3, "ACTE II"), Some(Unit::Act));
+        assert_eq!(structure.unit(2, "Scène I"), Some(Unit::Scene));
+        assert_eq!(structure.unit(1, "第一幕　湯島"), Some(Unit::Act));
+        assert_eq!(structure.unit(4, "第二場"), Some(Unit::Scene));
+        assert_eq!(structure.unit(4, "広場"), None);
+        assert_eq!(structure.unit(2, "Prologue"), Some(Unit::Act));
+    }
+
+    #[test]
+    fn recognize_japanese_headings() {
+        let structure = Structure::japanese();
+        assert_eq!(structure.unit(1, "第一幕"), Some(Unit::Act));
+        assert_eq!(structure.unit(2, "第一場"), Some(Unit::Scene));
+        assert_eq!(structure.unit(3, "湯島"), Some(Unit::Scene));
+    }
+}
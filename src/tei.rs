@@ -0,0 +1,377 @@
+//! TEI P5 output of play scripts.
+//!
+//! `TeiDocument` writes a `model::Play` as a TEI document for drama.
+//! Acts and scenes become `<div type="act">` and `<div type="scene">`, speeches become
+//! `<sp>` elements with `<speaker>`, and directions become `<stage>` elements.
+//! Characters are listed in `<castList>` and referred by the `who` attribute of `<sp>`.
+use pulldown_cmark::escape::escape_html;
+use crate::model::{Play, Block, Speech, Inline};
+
+/// Returns an `xml:id` for the name of a character.
+///
+/// Letters and digits are lowercased and the other characters are replaced with `-`.
+/// The ID is prefixed with `ch-` if it does not start with a letter.
+pub fn character_id(name: &str) -> String {
+    let mut id = String::new();
+
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            id.extend(c.to_lowercase());
+        } else if !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+
+    let id = id.trim_matches('-');
+    match id.chars().next() {
+        Some(c) if c.is_alphabetic() => id.to_owned(),
+        _ => format!("ch-{}", id),
+    }
+}
+
+/// Assigns unique IDs to the characters of a play in order of their appearance.
+fn character_ids(play: &Play) -> Vec<(String, String)> {
+    let mut ids: Vec<(String, String)> = Vec::new();
+
+    for character in play.characters.iter() {
+        let base = character_id(character);
+        let mut id = base.clone();
+        let mut count = 1;
+        while ids.iter().any(|(_, other)| *other == id) {
+            count += 1;
+            id = format!("{}-{}", base, count);
+        }
+        ids.push((character.clone(), id));
+    }
+
+    ids
+}
+
+fn escape(s: &mut String, text: &str) {
+    escape_html(&mut *s, text).unwrap();
+}
+
+fn push_line(s: &mut String, depth: usize, line: &str) {
+    for _ in 0..depth {
+        *s += "  ";
+    }
+    *s += line;
+    s.push('\n');
+}
+
+fn push_element(s: &mut String, depth: usize, tag: &str, attributes: &str, content: &str) {
+    push_line(s, depth, &format!("<{}{}>{}</{}>", tag, attributes, content, tag));
+}
+
+fn push_text_element(s: &mut String, depth: usize, tag: &str, attributes: &str, text: &str) {
+    let mut content = String::new();
+    escape(&mut content, text);
+    push_element(s, depth, tag, attributes, &content);
+}
+
+fn push_inline(s: &mut String, inline: &Inline) {
+    match inline {
+        Inline::Text(text) | Inline::Code(text) => escape(s, text),
+        Inline::Html(_) => {},
+        Inline::SoftBreak => s.push(' '),
+        Inline::HardBreak => *s += "<lb/>",
+        Inline::Emphasis(v) => push_hi(s, "italic", v),
+        Inline::Strong(v) => push_hi(s, "bold", v),
+        Inline::Strikethrough(v) => push_hi(s, "strikethrough", v),
+        Inline::Link { url, content, .. } => {
+            *s += "<ref target=\"";
+            escape(s, url);
+            *s += "\">";
+            push_inlines(s, content);
+            *s += "</ref>";
+        },
+        Inline::Image { content, .. } => push_inlines(s, content),
+        Inline::Direction(v) => {
+            *s += "<stage type=\"business\">";
+            push_inlines(s, v);
+            *s += "</stage>";
+        },
+    }
+}
+
+fn push_inlines(s: &mut String, inlines: &[Inline]) {
+    for inline in inlines.iter() {
+        push_inline(s, inline);
+    }
+}
+
+fn push_hi(s: &mut String, rend: &str, inlines: &[Inline]) {
+    *s += "<hi rend=\"";
+    *s += rend;
+    *s += "\">";
+    push_inlines(s, inlines);
+    *s += "</hi>";
+}
+
+fn inlines_to_xml(inlines: &[Inline]) -> String {
+    let mut s = String::new();
+    push_inlines(&mut s, inlines);
+    s.trim().to_owned()
+}
+
+/// A writer of a TEI document.
+#[derive(Debug,Clone,Default)]
+pub struct TeiDocument {
+    verse: bool,
+}
+
+impl TeiDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether each line of speeches is written as `<l>` instead of a single `<p>`.
+    pub fn verse(self, val: bool) -> Self {
+        Self {
+            verse: val,
+        }
+    }
+
+    fn push_header(&self, s: &mut String, play: &Play) {
+        push_line(s, 1, "<teiHeader>");
+        push_line(s, 2, "<fileDesc>");
+        push_line(s, 3, "<titleStmt>");
+        push_text_element(s, 4, "title", "", play.title.as_deref().unwrap_or(""));
+        if let Some(subtitle) = play.subtitle.as_ref() {
+            push_text_element(s, 4, "title", " type=\"sub\"", subtitle);
+        }
+        for author in play.authors.iter() {
+            push_text_element(s, 4, "author", "", author);
+        }
+        push_line(s, 3, "</titleStmt>");
+        push_line(s, 3, "<publicationStmt>");
+        push_element(s, 4, "p", "", "Converted by mdplayscript");
+        push_line(s, 3, "</publicationStmt>");
+        push_line(s, 3, "<sourceDesc>");
+        push_element(s, 4, "p", "", "Markdown play script");
+        push_line(s, 3, "</sourceDesc>");
+        push_line(s, 2, "</fileDesc>");
+        push_line(s, 1, "</teiHeader>");
+    }
+
+    fn push_cast_list(&self, s: &mut String, ids: &[(String, String)]) {
+        if ids.is_empty() {
+            return;
+        }
+
+        push_line(s, 2, "<front>");
+        push_line(s, 3, "<castList>");
+        for (character, id) in ids.iter() {
+            let mut line = String::from("<castItem><role xml:id=\"");
+            escape(&mut line, id);
+            line += "\">";
+            escape(&mut line, character);
+            line += "</role></castItem>";
+            push_line(s, 4, &line);
+        }
+        push_line(s, 3, "</castList>");
+        push_line(s, 2, "</front>");
+    }
+
+    fn push_speech(&self, s: &mut String, depth: usize, speech: &Speech, ids: &[(String, String)]) {
+        let who = ids.iter()
+            .find(|(character, _)| *character == speech.character)
+            .map(|(_, id)| id.as_str())
+            .unwrap_or("");
+        let mut start = String::from("<sp who=\"#");
+        escape(&mut start, who);
+        start += "\">";
+
+        push_line(s, depth, &start);
+        push_text_element(s, depth + 1, "speaker", "", &speech.character);
+        if !speech.direction.is_empty() {
+            push_element(s, depth + 1, "stage", " type=\"delivery\"", &inlines_to_xml(&speech.direction));
+        }
+        self.push_body(s, depth + 1, &speech.body);
+        push_line(s, depth, "</sp>");
+    }
+
+    fn push_body(&self, s: &mut String, depth: usize, body: &[Inline]) {
+        if !self.verse {
+            let content = inlines_to_xml(body);
+            if !content.is_empty() {
+                push_element(s, depth, "p", "", &content);
+            }
+            return;
+        }
+
+        for line in body.split(|inline| matches!(inline, Inline::SoftBreak | Inline::HardBreak)) {
+            let content = inlines_to_xml(line);
+            if !content.is_empty() {
+                push_element(s, depth, "l", "", &content);
+            }
+        }
+    }
+
+    fn push_block(&self, s: &mut String, depth: usize, block: &Block, ids: &[(String, String)]) {
+        match block {
+            Block::Speech(speech) => self.push_speech(s, depth, speech, ids),
+            Block::Direction { content } => {
+                for inline in content.iter() {
+                    if let Inline::Direction(direction) = inline {
+                        push_element(s, depth, "stage", " type=\"business\"", &inlines_to_xml(direction));
+                    }
+                }
+            },
+            Block::Monologue { content } => {
+                push_line(s, depth, "<sp>");
+                self.push_body(s, depth + 1, content);
+                push_line(s, depth, "</sp>");
+            },
+            Block::Prose { content } => {
+                push_element(s, depth, "p", "", &inlines_to_xml(content));
+            },
+            Block::Heading { level, content } => {
+                let attributes = format!(" rend=\"h{}\"", level);
+                push_element(s, depth, "p", &attributes, &inlines_to_xml(content));
+            },
+        }
+    }
+
+    /// Writes a play as a TEI document.
+    pub fn push_tei(&self, s: &mut String, play: &Play) {
+        let ids = character_ids(play);
+
+        *s += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+        *s += "<TEI xmlns=\"http://www.tei-c.org/ns/1.0\">\n";
+        self.push_header(s, play);
+        push_line(s, 1, "<text>");
+        self.push_cast_list(s, &ids);
+        push_line(s, 2, "<body>");
+
+        for act in play.acts.iter() {
+            let mut depth = 3;
+            if let Some(title) = act.title.as_ref() {
+                push_line(s, depth, "<div type=\"act\">");
+                push_text_element(s, depth + 1, "head", "", title);
+                depth += 1;
+            }
+
+            for scene in act.scenes.iter() {
+                let mut depth = depth;
+                if let Some(title) = scene.title.as_ref() {
+                    push_line(s, depth, "<div type=\"scene\">");
+                    push_text_element(s, depth + 1, "head", "", title);
+                    depth += 1;
+                }
+
+                for block in scene.blocks.iter() {
+                    self.push_block(s, depth, block, &ids);
+                }
+
+                if scene.title.is_some() {
+                    push_line(s, depth - 1, "</div>");
+                }
+            }
+
+            if act.title.is_some() {
+                push_line(s, 3, "</div>");
+            }
+        }
+
+        push_line(s, 2, "</body>");
+        push_line(s, 1, "</text>");
+        *s += "</TEI>\n";
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use crate::{Options, Params};
+
+    fn parse(s: &str) -> Play {
+        let params = Params {
+            title: Some("Le Mariage de Figaro".to_owned()),
+            subtitle: None,
+            authors: vec!["Beaumarchais".to_owned()],
+        };
+        Play::from_events(Parser::new(s), &Options::default(), params)
+    }
+
+    #[test]
+    fn make_character_ids() {
+        assert_eq!(character_id("Figaro"), "figaro");
+        assert_eq!(character_id("Le Comte Almaviva"), "le-comte-almaviva");
+        assert_eq!(character_id("お蔦"), "お蔦");
+        assert_eq!(character_id("2nd Gardener"), "ch-2nd-gardener");
+
+        let play = parse("A-B> x\nA B> y");
+        assert_eq!(character_ids(&play), vec![
+            ("A-B".to_owned(), "a-b".to_owned()),
+            ("A B".to_owned(), "a-b-2".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn export_play_to_tei() {
+        let s = r#"## Acte I
+
+### Scène 1
+
+Figaro (mesurant)> Dix-neuf *pieds*. (il sort)
+
+(Suzanne s'assied.)
+"#;
+        let mut buf = String::new();
+        TeiDocument::new().push_tei(&mut buf, &parse(s));
+        assert_eq!(buf, r##"<?xml version="1.0" encoding="UTF-8"?>
+<TEI xmlns="http://www.tei-c.org/ns/1.0">
+  <teiHeader>
+    <fileDesc>
+      <titleStmt>
+        <title>Le Mariage de Figaro</title>
+        <author>Beaumarchais</author>
+      </titleStmt>
+      <publicationStmt>
+        <p>Converted by mdplayscript</p>
+      </publicationStmt>
+      <sourceDesc>
+        <p>Markdown play script</p>
+      </sourceDesc>
+    </fileDesc>
+  </teiHeader>
+  <text>
+    <front>
+      <castList>
+        <castItem><role xml:id="figaro">Figaro</role></castItem>
+      </castList>
+    </front>
+    <body>
+      <div type="act">
+        <head>Acte I</head>
+        <div type="scene">
+          <head>Scène 1</head>
+          <sp who="#figaro">
+            <speaker>Figaro</speaker>
+            <stage type="delivery">mesurant</stage>
+            <p>Dix-neuf <hi rend="italic">pieds</hi>. <stage type="business">il sort</stage></p>
+          </sp>
+          <stage type="business">Suzanne s'assied.</stage>
+        </div>
+      </div>
+    </body>
+  </text>
+</TEI>
+"##);
+    }
+
+    #[test]
+    fn export_verse_to_tei() {
+        let mut buf = String::new();
+        TeiDocument::new().verse(true).push_tei(&mut buf, &parse("A> First line,\nsecond line."));
+        assert!(buf.contains(r##"      <sp who="#a">
+        <speaker>A</speaker>
+        <l>First line,</l>
+        <l>second line.</l>
+      </sp>
+"##));
+    }
+}
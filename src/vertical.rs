@@ -0,0 +1,103 @@
+//! Vertical writing (tategaki) of Japanese play scripts.
+//!
+//! `Options::vertical` makes `HtmlRenderer` render for the style sheet `play_ja_vertical.css`,
+//! which sets `writing-mode: vertical-rl` so that columns and pages flow from right to left.
+//! Short runs of numerals are set horizontally in a column (tate-chu-yoko) by wrapping them in
+//! `<span class="tcy">`, and the direction in a speech heading is placed before the character
+//! as a stage direction.
+use pulldown_cmark::Event;
+
+/// The maximum number of numerals which are combined into one character cell.
+/// Longer numbers are left to the text orientation of the style sheet.
+pub const TCY_MAX_DIGITS: usize = 2;
+
+/// Splits a text into plain texts and runs of numerals set in tate-chu-yoko.
+/// Returns pairs of a text and whether it is a run of numerals.
+pub fn split_tate_chu_yoko(s: &str) -> Vec<(&str, bool)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if !c.is_ascii_digit() {
+            continue;
+        }
+
+        let mut end = index + 1;
+        while let Some((next, c)) = chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            end = next + 1;
+            chars.next();
+        }
+
+        if end - index <= TCY_MAX_DIGITS {
+            if start < index {
+                segments.push((&s[start..index], false));
+            }
+            segments.push((&s[index..end], true));
+            start = end;
+        }
+    }
+
+    if start < s.len() {
+        segments.push((&s[start..], false));
+    }
+
+    segments
+}
+
+/// Wraps short runs of numerals in text events with `<span class="tcy">`.
+pub fn tate_chu_yoko<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut converted = Vec::new();
+
+    for event in events.into_iter() {
+        let s = match event {
+            Event::Text(s) => s,
+            event => {
+                converted.push(event);
+                continue;
+            },
+        };
+
+        let segments = split_tate_chu_yoko(&s);
+        if !segments.iter().any(|(_, is_tcy)| *is_tcy) {
+            converted.push(Event::Text(s));
+            continue;
+        }
+
+        for (text, is_tcy) in segments.into_iter() {
+            if is_tcy {
+                converted.push(Event::Html("<span class=\"tcy\">".into()));
+                converted.push(Event::Text(text.to_owned().into()));
+                converted.push(Event::Html("</span>".into()));
+            } else {
+                converted.push(Event::Text(text.to_owned().into()));
+            }
+        }
+    }
+
+    converted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn split_short_numerals() {
+        assert_eq!(split_tate_chu_yoko("12月3日"), vec![("12", true), ("月", false), ("3", true), ("日", false)]);
+        assert_eq!(split_tate_chu_yoko("明治2026年"), vec![("明治2026年", false)]);
+        assert_eq!(split_tate_chu_yoko("湯島"), vec![("湯島", false)]);
+    }
+
+    #[test]
+    fn wrap_numerals_into_spans() {
+        let events = tate_chu_yoko(vec![Event::Text("午後10時".into())]);
+        let mut buf = String::new();
+        pulldown_cmark::html::push_html(&mut buf, events.into_iter());
+        assert_eq!(buf, r#"午後<span class="tcy">10</span>時"#);
+    }
+}
@@ -18,7 +18,7 @@ impl<'a> SinglePageExample<'a> {
 
         let mut cmd = Command::new("cargo");
         cmd.env("RUST_LOG", "info");
-        cmd.args(&["run", "--example", "single", "--"]);
+        cmd.args(&["run", "--bin", "mdplayscript", "--", "build"]);
         if !self.authors.is_empty() {
             cmd.arg("--authors");
             for author in self.authors.iter() {
@@ -50,6 +50,12 @@ fn generate_examples() {
         .as_str().unwrap();
     assert_eq!(name, "mdplayscript");
 
+    // The generated documents link to the style sheets, which are kept in `assets`.
+    std::fs::create_dir_all("public").unwrap();
+    for style in ["play.css", "play_ja.css"].iter() {
+        std::fs::copy(format!("assets/{}", style), format!("public/{}", style)).unwrap();
+    }
+
     SinglePageExample {
         input: "examples/figaro.md",
         output: "public/figaro.html",